@@ -8,6 +8,37 @@ pub struct Config {
     files: Vec<String>,
     number_lines: bool,
     number_nonblank_lines: bool,
+    offsets: Option<OffsetFormat>,
+    banners: bool,
+    tab_stop: Option<usize>,
+    squeeze_blank: bool,
+    show_tabs: bool,
+    show_ends: bool,
+    show_nonprinting: bool,
+    reverse: bool,
+    start_number: usize,
+}
+
+#[derive(Debug)]
+enum OffsetFormat {
+    Dec,
+    Hex,
+}
+
+/// Yields each line without its terminator, along with the byte offset at
+/// which it starts. Control and meta characters are made visible with
+/// `show_nonprinting`. Tabs are expanded to spaces when a tab stop is set or
+/// shown as ^I with `show_tabs`, and with `squeeze_blank` only the first of
+/// several consecutive empty lines is yielded; offsets still refer to the
+/// input.
+struct OffsetLines {
+    buffer: Box<dyn BufRead>,
+    offset: u64,
+    tab_stop: Option<usize>,
+    show_tabs: bool,
+    show_nonprinting: bool,
+    squeeze_blank: bool,
+    prev_blank: bool,
 }
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
@@ -40,15 +71,128 @@ pub fn get_args() -> MyResult<Config> {
                 .long("number-nonblank")
                 .conflicts_with("number_lines"),
         )
+        .arg(
+            Arg::with_name("start_number")
+                .help("First line number for -n and -b")
+                .long("start-number")
+                .value_name("N")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("offsets")
+                .help("Prefix lines with their starting byte offset")
+                .takes_value(false)
+                .long("offsets"),
+        )
+        .arg(
+            Arg::with_name("offset_format")
+                .help("Format of byte offsets")
+                .long("offset-format")
+                .value_name("FORMAT")
+                .possible_values(&["dec", "hex"])
+                .requires("offsets"),
+        )
+        .arg(
+            Arg::with_name("banners")
+                .help("Print a ==> FILE <== header before each file")
+                .takes_value(false)
+                .long("banners"),
+        )
+        .arg(
+            Arg::with_name("squeeze_blank")
+                .help("Suppress repeated empty output lines")
+                .takes_value(false)
+                .short("s")
+                .long("squeeze-blank"),
+        )
+        .arg(
+            Arg::with_name("show_tabs")
+                .help("Display TAB characters as ^I")
+                .takes_value(false)
+                .short("T")
+                .long("show-tabs")
+                .conflicts_with("expand_tabs"),
+        )
+        .arg(
+            Arg::with_name("show_ends")
+                .help("Display $ at the end of each line")
+                .takes_value(false)
+                .short("E")
+                .long("show-ends"),
+        )
+        .arg(
+            Arg::with_name("show_nonprinting")
+                .help("Use ^ and M- notation, except for TAB")
+                .takes_value(false)
+                .short("v")
+                .long("show-nonprinting"),
+        )
+        .arg(
+            Arg::with_name("show_all")
+                .help("Equivalent to -vET")
+                .takes_value(false)
+                .short("A")
+                .long("show-all")
+                .conflicts_with("expand_tabs"),
+        )
+        .arg(
+            Arg::with_name("reverse")
+                .help("Print the lines of each file last to first, reading it fully first")
+                .takes_value(false)
+                .long("reverse"),
+        )
+        .arg(
+            Arg::with_name("expand_tabs")
+                .help("Expand tabs to spaces with tab stops every N columns")
+                .long("expand-tabs")
+                .value_name("N"),
+        )
         .get_matches();
 
+    let offsets = if matches.is_present("offsets") {
+        match matches.value_of("offset_format") {
+            Some("hex") => Some(OffsetFormat::Hex),
+            _ => Some(OffsetFormat::Dec),
+        }
+    } else {
+        None
+    };
+
+    let show_all = matches.is_present("show_all");
+
     Ok(Config {
         files: matches.values_of_lossy("files").unwrap(),
         number_lines: matches.is_present("number_lines"),
         number_nonblank_lines: matches.is_present("number_nonblank_lines"),
+        offsets,
+        banners: matches.is_present("banners"),
+        tab_stop: matches
+            .value_of("expand_tabs")
+            .map(|n| match n.parse() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(format!("Invalid --expand-tabs \"{}\"", n)),
+            })
+            .transpose()?,
+        squeeze_blank: matches.is_present("squeeze_blank"),
+        show_tabs: show_all || matches.is_present("show_tabs"),
+        show_ends: show_all || matches.is_present("show_ends"),
+        show_nonprinting: show_all || matches.is_present("show_nonprinting"),
+        reverse: matches.is_present("reverse"),
+        start_number: matches
+            .value_of("start_number")
+            .map(|n| parse_positive_int(n).map_err(|_| format!("Invalid --start-number \"{}\"", n)))
+            .transpose()?
+            .unwrap(),
     })
 }
 
+fn parse_positive_int(val: &str) -> MyResult<usize> {
+    match val.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(From::from(val)),
+    }
+}
+
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     match filename {
         "-" => Ok(Box::new(BufReader::new(io::stdin()))),
@@ -57,54 +201,214 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
 }
 
 pub fn run(config: Config) -> MyResult<()> {
-    for filename in config.files {
-        match open(&filename) {
-            Err(err) => eprintln!("Failed to open {}: {}", filename, err),
+    let mut printed_any = false;
+    let mut failed = 0;
+    // Numbering carries on from one file to the next
+    let mut line_number = config.start_number - 1;
+    let total = config.files.len();
+    for filename in &config.files {
+        match open(filename) {
+            Err(err) => {
+                eprintln!("Failed to open {}: {}", filename, err);
+                failed += 1;
+            }
             Ok(buffer) => {
-                if config.number_lines {
-                    print_number_lines(buffer)?;
-                } else if config.number_nonblank_lines {
-                    print_number_nonblank_lines(buffer)?;
-                } else {
-                    print_lines(buffer)?;
+                if config.banners {
+                    if printed_any {
+                        println!();
+                    }
+                    println!("==> {} <==", filename);
+                    printed_any = true;
+                }
+
+                // A file that fails partway through does not stop the others
+                if let Err(err) = print_file(buffer, &config, &mut line_number) {
+                    eprintln!("Failed to read {}: {}", filename, err);
+                    failed += 1;
                 }
             }
         }
     }
 
+    if failed > 0 {
+        return Err(From::from(format!(
+            "could not read {} of {} files",
+            failed, total
+        )));
+    }
     Ok(())
 }
 
-fn print_number_lines(buffer: Box<dyn BufRead>) -> MyResult<()> {
-    for (i, line) in buffer.lines().enumerate() {
-        println!("{:>6}\t{}", i + 1, line?);
+fn print_file(
+    mut buffer: Box<dyn BufRead>,
+    config: &Config,
+    line_number: &mut usize,
+) -> MyResult<()> {
+    if is_verbatim(config) {
+        io::copy(&mut buffer, &mut io::stdout().lock())?;
+        return Ok(());
+    }
+    let lines = OffsetLines {
+        buffer,
+        offset: 0,
+        tab_stop: config.tab_stop,
+        show_tabs: config.show_tabs,
+        show_nonprinting: config.show_nonprinting,
+        squeeze_blank: config.squeeze_blank,
+        prev_blank: false,
+    };
+    if config.reverse {
+        // The whole file is held in memory to print it backwards
+        let formatted = format_lines(lines, config, line_number).collect::<io::Result<Vec<_>>>()?;
+        for line in formatted.iter().rev() {
+            println!("{}", line);
+        }
+    } else {
+        for line in format_lines(lines, config, line_number) {
+            println!("{}", line?);
+        }
     }
-
     Ok(())
 }
 
-fn print_number_nonblank_lines(buffer: Box<dyn BufRead>) -> MyResult<()> {
-    let mut empty_line_count = 0;
+// Adds the offset, line number and end marker each option asks for. Numbers
+// count the lines as read, so they stay attached to their lines with --reverse;
+// `line_number` is the last number used
+fn format_lines<'a>(
+    lines: OffsetLines,
+    config: &'a Config,
+    line_number: &'a mut usize,
+) -> impl Iterator<Item = io::Result<String>> + 'a {
+    let end = line_end(config);
+    lines.map(move |line| {
+        let (offset, line) = line?;
+        let offset = format_offset(offset, &config.offsets);
+        // -b leaves blank lines unnumbered and does not count them
+        if config.number_lines || (config.number_nonblank_lines && !line.is_empty()) {
+            *line_number += 1;
+            Ok(format!("{}{:>6}\t{}{}", offset, line_number, line, end))
+        } else {
+            Ok(format!("{}{}{}", offset, line, end))
+        }
+    })
+}
+
+// Without any option that numbers or rewrites lines the input is copied as is,
+// so binary data and invalid UTF-8 pass through untouched
+fn is_verbatim(config: &Config) -> bool {
+    !(config.number_lines
+        || config.number_nonblank_lines
+        || config.offsets.is_some()
+        || config.tab_stop.is_some()
+        || config.squeeze_blank
+        || config.show_tabs
+        || config.show_ends
+        || config.show_nonprinting
+        || config.reverse)
+}
 
-    for (i, line) in buffer.lines().enumerate() {
-        let line = line?;
+fn line_end(config: &Config) -> &'static str {
+    if config.show_ends {
+        "$"
+    } else {
+        ""
+    }
+}
 
-        if line.is_empty() {
-            empty_line_count += 1;
-            println!();
+fn format_offset(offset: u64, format: &Option<OffsetFormat>) -> String {
+    match format {
+        Some(OffsetFormat::Dec) => format!("{:>8}\t", offset),
+        Some(OffsetFormat::Hex) => format!("{:08x}\t", offset),
+        None => "".to_string(),
+    }
+}
+
+// Control characters other than tab become ^X (DEL is ^?) and bytes above 127 are
+// written as M- followed by the same rendering of their low seven bits, as in GNU cat -v
+fn show_nonprinting(bytes: &[u8]) -> String {
+    let mut visible = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        let low = if byte >= 128 {
+            visible.push_str("M-");
+            byte - 128
         } else {
-            let line_number = i + 1 - empty_line_count;
-            println!("{:>6}\t{}", line_number, line);
+            byte
+        };
+        match low {
+            b'\t' if byte == low => visible.push('\t'),
+            0..=31 => {
+                visible.push('^');
+                visible.push((low + 64) as char);
+            }
+            127 => visible.push_str("^?"),
+            _ => visible.push(low as char),
         }
     }
-
-    Ok(())
+    visible
 }
 
-fn print_lines(buffer: Box<dyn BufRead>) -> MyResult<()> {
-    for line in buffer.lines() {
-        println!("{}", line?);
+// Each tab advances to the next multiple of `width` columns, counting chars
+fn expand_tabs(line: &str, width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = width - column % width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
     }
+    expanded
+}
 
-    Ok(())
+impl Iterator for OffsetLines {
+    type Item = io::Result<(u64, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = Vec::new();
+        loop {
+            bytes.clear();
+            match self.buffer.read_until(b'\n', &mut bytes) {
+                Ok(0) => return None,
+                Ok(n) => {
+                    let start = self.offset;
+                    self.offset += n as u64;
+                    if bytes.ends_with(b"\n") {
+                        bytes.pop();
+                        // -v shows the carriage return of a CRLF ending as ^M
+                        if bytes.ends_with(b"\r") && !self.show_nonprinting {
+                            bytes.pop();
+                        }
+                    }
+                    let blank = bytes.is_empty();
+                    if self.squeeze_blank && blank && self.prev_blank {
+                        continue;
+                    }
+                    self.prev_blank = blank;
+                    let line = if self.show_nonprinting {
+                        show_nonprinting(&bytes)
+                    } else {
+                        match String::from_utf8(bytes) {
+                            Ok(line) => line,
+                            Err(_) => {
+                                return Some(Err(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    "stream did not contain valid UTF-8",
+                                )))
+                            }
+                        }
+                    };
+                    return match self.tab_stop {
+                        Some(width) => Some(Ok((start, expand_tabs(&line, width)))),
+                        None if self.show_tabs => Some(Ok((start, line.replace('\t', "^I")))),
+                        None => Some(Ok((start, line))),
+                    };
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
 }
@@ -47,11 +47,24 @@ fn skips_bad_file() -> TestResult {
     Command::cargo_bin(PRG)?
         .arg(&bad)
         .assert()
-        .success()
+        .failure()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn bad_file_exits_nonzero() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args([&bad, FOX])
+        .assert()
+        .code(1)
+        .stdout("The quick brown fox jumps over the lazy dog.\n")
+        .stderr(predicate::str::ends_with("could not read 1 of 2 files\n"));
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> TestResult {
     let expected = fs::read_to_string(expected_file)?;
@@ -194,3 +207,327 @@ fn all_n() -> TestResult {
 fn all_b() -> TestResult {
     run(&[FOX, SPIDERS, BUSTLE, "-b"], "tests/expected/all.b.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn spiders_offsets() -> TestResult {
+    run(
+        &["--offsets", SPIDERS],
+        "tests/expected/spiders.txt.offsets.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_offsets_dec() -> TestResult {
+    run(
+        &["--offsets", "--offset-format", "dec", SPIDERS],
+        "tests/expected/spiders.txt.offsets.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_offsets_hex_n() -> TestResult {
+    run(
+        &["--offsets", "--offset-format", "hex", "-n", SPIDERS],
+        "tests/expected/spiders.txt.offsets.hex.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn offsets_crlf_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--offsets", "-b"])
+        .write_stdin("a\r\n\r\nbc\nd")
+        .assert()
+        .success()
+        .stdout("       0\t     1\ta\n       3\t\n       5\t     2\tbc\n       8\t     3\td\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_spiders_banners() -> TestResult {
+    run(
+        &["--banners", FOX, SPIDERS],
+        "tests/expected/fox_spiders.banners.out",
+    )?;
+
+    // No banners unless asked for
+    Command::cargo_bin(PRG)?
+        .args([FOX, SPIDERS])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("==>").not());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn expand_tabs() -> TestResult {
+    let input = "\tlead\na\tb\nabc\td\nabcd\te\na\t\tf\né\tg\n";
+    Command::cargo_bin(PRG)?
+        .args(["--expand-tabs", "4"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("    lead\na   b\nabc d\nabcd    e\na       f\né   g\n");
+
+    // Line numbers are not counted towards the columns
+    Command::cargo_bin(PRG)?
+        .args(["--expand-tabs", "8", "-n"])
+        .write_stdin("ab\tc\n")
+        .assert()
+        .success()
+        .stdout("     1\tab      c\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_expand_tabs() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--expand-tabs", "0", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(r#"Invalid --expand-tabs "0""#));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn squeeze_blank() -> TestResult {
+    let input = "\n\na\n\n\n\nb\n\nc\n\n\n";
+    for (args, expected) in [
+        (vec!["-s"], "\na\n\nb\n\nc\n\n"),
+        (
+            vec!["-s", "-n"],
+            "     1\t\n     2\ta\n     3\t\n     4\tb\n     5\t\n     6\tc\n     7\t\n",
+        ),
+        (
+            vec!["-s", "-b"],
+            "\n     1\ta\n\n     2\tb\n\n     3\tc\n\n",
+        ),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn show_tabs_ends() -> TestResult {
+    let input = "a\tb  c\n\n\t\td \n";
+    for (args, expected) in [
+        (vec!["-T"], "a^Ib  c\n\n^I^Id \n"),
+        (vec!["-E"], "a\tb  c$\n$\n\t\td $\n"),
+        (
+            vec!["-T", "-E", "-n"],
+            "     1\ta^Ib  c$\n     2\t$\n     3\t^I^Id $\n",
+        ),
+        (
+            vec!["-T", "-E", "-b"],
+            "     1\ta^Ib  c$\n$\n     2\t^I^Id $\n",
+        ),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn show_nonprinting() -> TestResult {
+    let input: &[u8] = b"a\x01\tb\x7f\n\x80\x89\xff\xc3\xa9\r\nplain\n";
+    Command::cargo_bin(PRG)?
+        .arg("-v")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("a^A\tb^?\nM-^@M-^IM-^?M-CM-)^M\nplain\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-v", "-T", "-n"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("     1\ta^A^Ib^?\n     2\tM-^@M-^IM-^?M-CM-)^M\n     3\tplain\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn show_all() -> TestResult {
+    let input: &[u8] = b"a\tb\x18c\n\nd\n";
+    let expected = "a^Ib^Xc$\n$\nd$\n";
+    for args in [vec!["-A"], vec!["--show-all"], vec!["-v", "-E", "-T"]] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+
+    Command::cargo_bin(PRG)?
+        .args(["-A", "-b"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("     1\ta^Ib^Xc$\n$\n     2\td$\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn number_nonblank_interleaved() -> TestResult {
+    for (input, expected) in [
+        (
+            "\n\na\n\n\nb\nc\n\n",
+            "\n\n     1\ta\n\n\n     2\tb\n     3\tc\n\n",
+        ),
+        ("a\n\n\n\n\n\nb\n", "     1\ta\n\n\n\n\n\n     2\tb\n"),
+        ("\n\n\n", "\n\n\n"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .arg("-b")
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_passthrough() -> TestResult {
+    // Invalid UTF-8, NULs, a CRLF ending and no final newline are all kept
+    let input: &[u8] = b"\xff\xfe\x00bin\r\nary\x80";
+    Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(input);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn reverse() -> TestResult {
+    // The last line has no newline but still gets one
+    let input = "one\n\nthree\nfour";
+    for (args, expected) in [
+        (vec!["--reverse"], "four\nthree\n\none\n"),
+        (
+            vec!["--reverse", "-n"],
+            "     4\tfour\n     3\tthree\n     2\t\n     1\tone\n",
+        ),
+        (
+            vec!["--reverse", "-b"],
+            "     3\tfour\n     2\tthree\n\n     1\tone\n",
+        ),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+
+    // Files are reversed one at a time, in the order given
+    Command::cargo_bin(PRG)?
+        .args(["--reverse", FOX, "-"])
+        .write_stdin("a\nb\n")
+        .assert()
+        .success()
+        .stdout("The quick brown fox jumps over the lazy dog.\nb\na\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn unreadable_file_exits_nonzero() -> TestResult {
+    // Directories open fine on Unix but fail on the first read
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", FOX])
+        .assert()
+        .code(1)
+        .stdout("The quick brown fox jumps over the lazy dog.\n")
+        .stderr(predicate::str::contains("Failed to read tests/inputs: "))
+        .stderr(predicate::str::ends_with("could not read 1 of 2 files\n"));
+
+    // Invalid UTF-8 stops the line-based paths for that file only
+    let bad = tempfile::NamedTempFile::new()?;
+    fs::write(bad.path(), b"ok\n\xff\n")?;
+    Command::cargo_bin(PRG)?
+        .args(["-n", &bad.path().to_string_lossy(), FOX])
+        .assert()
+        .code(1)
+        .stdout("     1\tok\n     2\tThe quick brown fox jumps over the lazy dog.\n")
+        .stderr(predicate::str::contains(
+            "stream did not contain valid UTF-8",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn start_number() -> TestResult {
+    for (args, expected) in [
+        (
+            vec!["-n", "--start-number", "9"],
+            "     9\ta\n    10\t\n    11\tb\n",
+        ),
+        (
+            vec!["-b", "--start-number", "9"],
+            "     9\ta\n\n    10\tb\n",
+        ),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .write_stdin("a\n\nb\n")
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+
+    // Numbering continues across files
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--start-number", "5", FOX, "-"])
+        .write_stdin("a\n")
+        .assert()
+        .success()
+        .stdout("     5\tThe quick brown fox jumps over the lazy dog.\n     6\ta\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_start_number() -> TestResult {
+    for bad in ["0", "x", "1.5"] {
+        Command::cargo_bin(PRG)?
+            .args(["-n", "--start-number", bad, FOX])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "Invalid --start-number \"{}\"",
+                bad
+            )));
+    }
+    Ok(())
+}
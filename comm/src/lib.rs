@@ -17,6 +17,10 @@ pub struct Config {
     show_col3: bool,
     insensitive: bool,
     delimiter: String,
+    diff_only: bool,
+    key_field: Option<usize>,
+    input_delimiter: Option<String>,
+    json: bool,
 }
 
 enum Column<'a> {
@@ -73,6 +77,31 @@ pub fn get_args() -> MyResult<Config> {
                 .default_value("\t")
                 .help("Output delimiter"),
         )
+        .arg(
+            Arg::with_name("diff_only")
+                .long("diff-only")
+                .takes_value(false)
+                .help("Suppress column 3 and print a count of common lines"),
+        )
+        .arg(
+            Arg::with_name("key_field")
+                .long("key-field")
+                .value_name("FIELD")
+                .help("Compare only this field of each line"),
+        )
+        .arg(
+            Arg::with_name("input_delimiter")
+                .long("input-delimiter")
+                .value_name("DELIM")
+                .requires("key_field")
+                .help("Field separator for --key-field [default: output delimiter]"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help("Print one JSON object per line naming where it occurs"),
+        )
         .get_matches();
 
     Ok(Config {
@@ -80,9 +109,19 @@ pub fn get_args() -> MyResult<Config> {
         file2: matches.value_of_lossy("file2").unwrap().to_string(),
         show_col1: !matches.is_present("supress_col1"),
         show_col2: !matches.is_present("supress_col2"),
-        show_col3: !matches.is_present("supress_col3"),
+        show_col3: !(matches.is_present("supress_col3") || matches.is_present("diff_only")),
         insensitive: matches.is_present("insensitive"),
         delimiter: matches.value_of("delimiter").unwrap().to_string(),
+        diff_only: matches.is_present("diff_only"),
+        key_field: matches
+            .value_of("key_field")
+            .map(|f| match f.parse() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(format!("Invalid --key-field \"{}\"", f)),
+            })
+            .transpose()?,
+        input_delimiter: matches.value_of("input_delimiter").map(str::to_string),
+        json: matches.is_present("json"),
     })
 }
 
@@ -92,32 +131,33 @@ pub fn run(config: Config) -> MyResult<()> {
     if file1 == "-" && file2 == "-" {
         return Err(From::from("Both input files cannot be STDIN (\"-\")"));
     }
-    let mut lines1 = open(file1)?
-        .lines()
-        .collect::<Result<Vec<_>, _>>()?
-        .into_iter()
-        .map(|l| {
-            if config.insensitive {
-                l.to_lowercase()
-            } else {
-                l
-            }
-        });
-    let mut lines2 = open(file2)?
-        .lines()
-        .collect::<Result<Vec<_>, _>>()?
-        .into_iter()
-        .map(|l| {
-            if config.insensitive {
-                l.to_lowercase()
-            } else {
-                l
-            }
-        });
-    let mut line1 = lines1.next();
-    let mut line2 = lines2.next();
+    // Lines are streamed and normalized exactly once as they are read, so
+    // the comparison loop below never re-lowercases
+    let normalize = |line: String| {
+        if config.insensitive {
+            line.to_lowercase()
+        } else {
+            line
+        }
+    };
+    let mut lines1 = open(file1)?.lines().map(|l| l.map(normalize));
+    let mut lines2 = open(file2)?.lines().map(|l| l.map(normalize));
+    let mut line1 = lines1.next().transpose()?;
+    let mut line2 = lines2.next().transpose()?;
+    let mut common = 0;
 
     let print = |col: Column| {
+        if config.json {
+            let (shown, origin, val) = match col {
+                Column::Col1(val) => (config.show_col1, "file1", val),
+                Column::Col2(val) => (config.show_col2, "file2", val),
+                Column::Col3(val) => (config.show_col3, "both", val),
+            };
+            if shown {
+                println!("{}", serde_json::json!({ "in": origin, "line": val }));
+            }
+            return;
+        }
         let mut columns = vec![];
         match col {
             Column::Col1(val) => {
@@ -154,32 +194,49 @@ pub fn run(config: Config) -> MyResult<()> {
             (None, None) => break,
             (Some(l1), None) => {
                 print(Column::Col1(l1));
-                line1 = lines1.next();
+                line1 = lines1.next().transpose()?;
             }
             (None, Some(l2)) => {
                 print(Column::Col2(l2));
-                line2 = lines2.next();
+                line2 = lines2.next().transpose()?;
             }
-            (Some(l1), Some(l2)) => match l1.cmp(l2) {
+            (Some(l1), Some(l2)) => match compare_key(l1, &config).cmp(compare_key(l2, &config)) {
                 Equal => {
+                    common += 1;
                     print(Column::Col3(l1));
-                    line1 = lines1.next();
-                    line2 = lines2.next();
+                    line1 = lines1.next().transpose()?;
+                    line2 = lines2.next().transpose()?;
                 }
                 Less => {
                     print(Column::Col1(l1));
-                    line1 = lines1.next();
+                    line1 = lines1.next().transpose()?;
                 }
                 Greater => {
                     print(Column::Col2(l2));
-                    line2 = lines2.next();
+                    line2 = lines2.next().transpose()?;
                 }
             },
         }
     }
+    if config.diff_only && config.json {
+        println!("{}", serde_json::json!({ "common": common }));
+    } else if config.diff_only {
+        println!("common: {}", common);
+    }
     Ok(())
 }
 
+// With --key-field only that field is compared; lines missing it compare as
+// if the field were empty. Fields are split on --input-delimiter, falling back
+// to the output delimiter.
+fn compare_key<'a>(line: &'a str, config: &Config) -> &'a str {
+    let delimiter = config.input_delimiter.as_ref().unwrap_or(&config.delimiter);
+    match config.key_field {
+        Some(n) => line.split(delimiter.as_str()).nth(n - 1).unwrap_or(""),
+        None => line,
+    }
+}
+
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     match filename {
         "-" => Ok(Box::new(BufReader::new(io::stdin()))),
@@ -8,6 +8,10 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FILE1: &str = "tests/inputs/file1.txt";
 const FILE2: &str = "tests/inputs/file2.txt";
 const BLANK: &str = "tests/inputs/blank.txt";
+const CITIES1_LOWER: &str = "tests/inputs/cities1_lower.txt";
+const CITIES2_UPPER: &str = "tests/inputs/cities2_upper.txt";
+const KEYED1: &str = "tests/inputs/keyed1.csv";
+const KEYED2: &str = "tests/inputs/keyed2.csv";
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -42,7 +46,7 @@ fn dies_bad_file1() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&[&bad, FILE1])
+        .args([&bad, FILE1])
         .assert()
         .failure()
         .stderr(predicate::str::is_match(expected)?);
@@ -55,7 +59,7 @@ fn dies_bad_file2() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&[FILE1, &bad])
+        .args([FILE1, &bad])
         .assert()
         .failure()
         .stderr(predicate::str::is_match(expected)?);
@@ -67,7 +71,7 @@ fn dies_bad_file2() -> TestResult {
 fn dies_both_stdin() -> TestResult {
     let expected = "Both input files cannot be STDIN (\"-\")";
     Command::cargo_bin(PRG)?
-        .args(&["-", "-"])
+        .args(["-", "-"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -146,6 +150,24 @@ fn file1_file2_3() -> TestResult {
     run(&["-3", FILE1, FILE2], "tests/expected/file1_file2.3.out")
 }
 
+// --------------------------------------------------
+#[test]
+fn file1_file2_diff_only() -> TestResult {
+    run(
+        &["--diff-only", FILE1, FILE2],
+        "tests/expected/file1_file2.diff_only.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_diff_only_3() -> TestResult {
+    run(
+        &["--diff-only", "-3", FILE1, FILE2],
+        "tests/expected/file1_file2.diff_only.out",
+    )
+}
+
 // --------------------------------------------------
 #[test]
 fn file1_file2_1_2() -> TestResult {
@@ -238,6 +260,51 @@ fn file1_file2_123_i() -> TestResult {
     )
 }
 
+// --------------------------------------------------
+#[test]
+fn file1_file2_i() -> TestResult {
+    run(&["-i", FILE1, FILE2], "tests/expected/file1_file2.i.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn cities1_lower_cities2_upper_i() -> TestResult {
+    run(
+        &["-i", CITIES1_LOWER, CITIES2_UPPER],
+        "tests/expected/cities1_lower_cities2_upper.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn keyed1_keyed2_key_field() -> TestResult {
+    run(
+        &["--key-field", "2", "-d", ",", KEYED1, KEYED2],
+        "tests/expected/keyed1_keyed2.key2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn keyed1_keyed2_input_delimiter() -> TestResult {
+    // Keys are split on commas while output columns stay tab-separated
+    run(
+        &["--key-field", "2", "--input-delimiter", ",", KEYED1, KEYED2],
+        "tests/expected/keyed1_keyed2.key2.input_dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_key_field() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--key-field", "0", KEYED1, KEYED2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(r#"Invalid --key-field "0""#));
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn stdin_file1() -> TestResult {
@@ -336,6 +403,33 @@ fn blank_file1() -> TestResult {
     run(&[BLANK, FILE1], "tests/expected/blank_file1.out")
 }
 
+// --------------------------------------------------
+#[test]
+fn file1_file2_json() -> TestResult {
+    run(
+        &[FILE1, FILE2, "--json"],
+        "tests/expected/file1_file2.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_3_json() -> TestResult {
+    run(
+        &[FILE1, FILE2, "-3", "--json"],
+        "tests/expected/file1_file2.3.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_diff_only_json() -> TestResult {
+    run(
+        &[FILE1, FILE2, "--diff-only", "--json"],
+        "tests/expected/file1_file2.diff_only.json.out",
+    )
+}
+
 //// --------------------------------------------------
 //#[test]
 //fn file1_blanks() -> TestResult {
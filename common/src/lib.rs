@@ -0,0 +1,99 @@
+use std::io::{self, Write};
+
+const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+/// Formats a byte count using binary (1024-based) units, e.g. "512 B" or "1.2 KiB".
+pub fn humanize_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Length of a UTF-8 sequence cut off at the end of `bytes`, if any. Invalid
+/// bytes don't count, since no further input could complete them.
+pub fn incomplete_tail(bytes: &[u8]) -> usize {
+    let start = bytes.len().saturating_sub(3);
+    match bytes[start..].iter().rposition(|b| b & 0xC0 != 0x80) {
+        Some(i) => match std::str::from_utf8(&bytes[start + i..]) {
+            Err(e) if e.error_len().is_none() => bytes.len() - start - i,
+            _ => 0,
+        },
+        None => 0,
+    }
+}
+
+/// Writes records each followed by a terminator, '\n' normally or '\0' for
+/// the -z modes. Records are written as raw bytes, never re-encoded.
+pub struct LineWriter<W: Write> {
+    inner: W,
+    terminator: u8,
+}
+
+impl<W: Write> LineWriter<W> {
+    pub fn new(inner: W, terminator: u8) -> Self {
+        LineWriter { inner, terminator }
+    }
+
+    /// Writes `record` followed by the terminator.
+    pub fn write_record(&mut self, record: impl AsRef<[u8]>) -> io::Result<()> {
+        self.inner.write_all(record.as_ref())?;
+        self.inner.write_all(&[self.terminator])
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{humanize_bytes, incomplete_tail, LineWriter};
+
+    #[test]
+    fn test_humanize_bytes() {
+        assert_eq!(humanize_bytes(0), "0 B");
+        assert_eq!(humanize_bytes(1023), "1023 B");
+        assert_eq!(humanize_bytes(1024), "1.0 KiB");
+        assert_eq!(humanize_bytes(1229), "1.2 KiB");
+        assert_eq!(humanize_bytes(1024 * 1024), "1.0 MiB");
+        assert_eq!(humanize_bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_incomplete_tail() {
+        assert_eq!(incomplete_tail(b""), 0);
+        assert_eq!(incomplete_tail(b"abc"), 0);
+        assert_eq!(incomplete_tail("aś".as_bytes()), 0);
+        assert_eq!(incomplete_tail(&"aś".as_bytes()[..2]), 1);
+        assert_eq!(incomplete_tail(&"a€".as_bytes()[..3]), 2);
+        assert_eq!(incomplete_tail(&"😀".as_bytes()[..3]), 3);
+        assert_eq!(incomplete_tail("😀".as_bytes()), 0);
+        // Invalid bytes are replaced, not held back
+        assert_eq!(incomplete_tail(b"a\xff"), 0);
+    }
+
+    #[test]
+    fn test_line_writer() {
+        let mut writer = LineWriter::new(vec![], b'\n');
+        writer.write_record("one").unwrap();
+        writer.write_record(b"two\xff").unwrap();
+        writer.write_record("").unwrap();
+        assert_eq!(writer.into_inner(), b"one\ntwo\xff\n\n");
+
+        let mut writer = LineWriter::new(vec![], b'\0');
+        writer.write_record("one\ntwo").unwrap();
+        writer.write_record(String::from("three")).unwrap();
+        assert_eq!(writer.into_inner(), b"one\ntwo\0three\0");
+    }
+}
@@ -1,10 +1,21 @@
 use clap::{App, Arg};
+use common::LineWriter;
+use regex::Regex;
 use std::io::{self, BufRead, BufReader};
-use std::{error::Error, fs::File, ops::Range};
+use std::{borrow::Cow, error::Error, fs, fs::File, ops::Range};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 type PositionList = Vec<Range<usize>>;
 
+// How many leading records --detect-delimiter inspects per file
+const DETECT_RECORDS: usize = 10;
+const CANDIDATE_DELIMITERS: [(u8, &str); 4] = [
+    (b'\t', "tab"),
+    (b',', "comma"),
+    (b';', "semicolon"),
+    (b'|', "pipe"),
+];
+
 #[derive(Debug)]
 pub enum Extract {
     Fields(PositionList),
@@ -17,6 +28,16 @@ pub struct Config {
     files: Vec<String>,
     delimiter: u8,
     extract: Extract,
+    trim: bool,
+    json_names: Option<Vec<String>>,
+    detect_delimiter: bool,
+    complement: bool,
+    only_delimited: bool,
+    output_delimiter: Option<String>,
+    preserve_chars: bool,
+    zero_terminated: bool,
+    error_on_missing: bool,
+    regex_delim: Option<Regex>,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -44,6 +65,7 @@ pub fn get_args() -> MyResult<Config> {
                 .short("b")
                 .long("bytes")
                 .value_name("BYTES")
+                .allow_hyphen_values(true)
                 .conflicts_with("chars")
                 .conflicts_with("fields")
                 .help("Selected bytes"),
@@ -53,6 +75,7 @@ pub fn get_args() -> MyResult<Config> {
                 .short("c")
                 .long("chars")
                 .value_name("CHARS")
+                .allow_hyphen_values(true)
                 .conflicts_with("bytes")
                 .conflicts_with("fields")
                 .help("Selected characters"),
@@ -62,30 +85,162 @@ pub fn get_args() -> MyResult<Config> {
                 .short("f")
                 .long("fields")
                 .value_name("FIELDS")
+                .allow_hyphen_values(true)
                 .conflicts_with("bytes")
                 .conflicts_with("chars")
                 .help("Selected fields"),
         )
+        .arg(
+            Arg::with_name("fields_file")
+                .long("fields-file")
+                .value_name("FILE")
+                .conflicts_with_all(&["bytes", "chars", "fields"])
+                .help("Read selected fields from a file"),
+        )
+        .arg(
+            Arg::with_name("template")
+                .long("template")
+                .value_name("FIELDS")
+                .allow_hyphen_values(true)
+                .conflicts_with_all(&["bytes", "chars", "fields", "fields_file"])
+                .help("Output fields in exactly this order, repeats allowed"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .requires("field_names")
+                .conflicts_with_all(&["bytes", "chars"])
+                .help("Print one JSON object per line keyed by --field-names"),
+        )
+        .arg(
+            Arg::with_name("field_names")
+                .long("field-names")
+                .value_name("NAMES")
+                .requires("json")
+                .help("Comma-separated names for the selected fields"),
+        )
+        .arg(
+            Arg::with_name("preserve_chars")
+                .long("preserve-chars")
+                .takes_value(false)
+                .requires("bytes")
+                .help("Widen byte ranges so no UTF-8 character is split"),
+        )
+        .arg(
+            Arg::with_name("trim")
+                .long("trim")
+                .takes_value(false)
+                .help("Trim whitespace around selected fields"),
+        )
+        .arg(
+            Arg::with_name("output_delimiter")
+                .long("output-delimiter")
+                .value_name("STRING")
+                .conflicts_with_all(&["bytes", "chars", "json"])
+                .help("Separate output fields with STRING [default: --delim]"),
+        )
+        .arg(
+            Arg::with_name("only_delimited")
+                .short("s")
+                .long("only-delimited")
+                .takes_value(false)
+                .conflicts_with_all(&["bytes", "chars"])
+                .help("Skip lines that do not contain the delimiter"),
+        )
+        .arg(
+            Arg::with_name("regex_delim")
+                .long("regex-delim")
+                .value_name("PATTERN")
+                .conflicts_with_all(&["bytes", "chars", "json", "detect_delimiter"])
+                .help(
+                    "Split fields on PATTERN; output is joined by --output-delimiter, else --delim",
+                ),
+        )
+        .arg(
+            Arg::with_name("error_on_missing")
+                .long("error-on-missing")
+                .takes_value(false)
+                .conflicts_with_all(&["bytes", "chars", "complement"])
+                .help("Fail if a selected field is missing from every record"),
+        )
+        .arg(
+            Arg::with_name("zero_terminated")
+                .short("z")
+                .long("zero-terminated")
+                .takes_value(false)
+                .help("Read and write NUL-terminated records instead of lines"),
+        )
+        .arg(
+            Arg::with_name("complement")
+                .long("complement")
+                .takes_value(false)
+                .conflicts_with_all(&["template", "json"])
+                .help("Select everything except the given positions"),
+        )
+        .arg(
+            Arg::with_name("detect_delimiter")
+                .long("detect-delimiter")
+                .takes_value(false)
+                .conflicts_with_all(&["bytes", "chars"])
+                .help("Suggest a delimiter when no record splits into fields"),
+        )
         .get_matches();
 
+    let fields_file = matches
+        .value_of("fields_file")
+        .map(read_positions)
+        .transpose()?;
     let extract = vec![
         matches.value_of_lossy("bytes"),
         matches.value_of_lossy("chars"),
         matches.value_of_lossy("fields"),
+        fields_file.map(Cow::Owned),
+        matches.value_of_lossy("template"),
     ]
     .into_iter()
     .find_map(|t| t)
     .ok_or("Must have --fields, --bytes, or --chars")?;
 
     let pos_vec = parse_pos(&extract)?;
-    let extract = if let true = matches.is_present("bytes") {
+    let extract = if matches.is_present("bytes") {
         Extract::Bytes(pos_vec)
-    } else if let true = matches.is_present("chars") {
+    } else if matches.is_present("chars") {
         Extract::Chars(pos_vec)
     } else {
         Extract::Fields(pos_vec)
     };
 
+    if matches.is_present("trim") && !matches!(extract, Extract::Fields(_)) {
+        return Err(From::from(
+            "--trim needs --fields, --fields-file or --template",
+        ));
+    }
+
+    // GNU cut rejects this outright; a warning keeps existing scripts working
+    if !matches!(extract, Extract::Fields(_)) && matches.occurrences_of("delimiter") > 0 {
+        eprintln!("warning: --delim is only used with --fields and will be ignored");
+    }
+
+    let json_names = match (&extract, matches.value_of("field_names")) {
+        (Extract::Fields(pos), Some(_)) if pos.iter().any(|r| r.end == usize::MAX) => {
+            return Err(From::from("--field-names cannot name an open-ended range"));
+        }
+        (Extract::Fields(pos), Some(names)) => {
+            let names: Vec<String> = names.split(',').map(str::to_string).collect();
+            let selected = pos.iter().map(ExactSizeIterator::len).sum::<usize>();
+            if names.len() != selected {
+                return Err(From::from(format!(
+                    "--field-names has {} names but {} fields are selected",
+                    names.len(),
+                    selected
+                )));
+            }
+            Some(names)
+        }
+        _ => None,
+    };
+
     let delimiter: MyResult<u8> = match matches.value_of_lossy("delimiter").unwrap().as_bytes() {
         b if b.len() == 1 => Ok(b[0]),
         b => Err(From::from(format!(
@@ -94,59 +249,229 @@ pub fn get_args() -> MyResult<Config> {
         ))),
     };
 
+    let regex_delim = match matches.value_of("regex_delim") {
+        Some(pattern) => Some(
+            Regex::new(pattern).map_err(|_| format!("Invalid --regex-delim \"{}\"", pattern))?,
+        ),
+        None => None,
+    };
+
     Ok(Config {
         files: matches.values_of_lossy("files").unwrap(),
         delimiter: delimiter?,
         extract,
+        trim: matches.is_present("trim"),
+        json_names,
+        detect_delimiter: matches.is_present("detect_delimiter"),
+        complement: matches.is_present("complement"),
+        only_delimited: matches.is_present("only_delimited"),
+        output_delimiter: matches.value_of("output_delimiter").map(str::to_string),
+        preserve_chars: matches.is_present("preserve_chars"),
+        zero_terminated: matches.is_present("zero_terminated"),
+        error_on_missing: matches.is_present("error_on_missing"),
+        regex_delim,
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
+    // With -z every record, including the last, ends in NUL and no
+    // newline is ever added to the output
+    let terminator = if config.zero_terminated { b'\0' } else { b'\n' };
+    let mut out = LineWriter::new(io::stdout(), terminator);
+    // Field count of the widest record across all files
+    let mut widest = 0;
     for filename in &config.files {
         match open(filename) {
             Err(err) => eprintln!("{}: {}", filename, err),
             Ok(file) => {
-                let buf_reader = BufReader::new(file);
+                let mut buf_reader = BufReader::new(file);
                 match &config.extract {
                     Extract::Bytes(pos) => {
-                        for line in buf_reader.lines() {
-                            let extracted = extract_bytes(&(line?), pos);
-                            println!("{}", extracted);
+                        for line in records(buf_reader, terminator) {
+                            let line = line?;
+                            let pos = selected(pos, line.len(), config.complement);
+                            out.write_record(extract_bytes(&line, &pos, config.preserve_chars))?;
                         }
                     }
                     Extract::Chars(pos) => {
-                        for line in buf_reader.lines() {
-                            let extracted = extract_chars(&(line?), pos);
-                            println!("{}", extracted);
+                        for line in records(buf_reader, terminator) {
+                            let line = line?;
+                            let pos = selected(pos, line.chars().count(), config.complement);
+                            out.write_record(extract_chars(&line, &pos))?;
                         }
                     }
-                    Extract::Fields(pos) => {
+                    Extract::Fields(pos) if config.regex_delim.is_some() => {
+                        // No quoting applies here, so fields are split and
+                        // joined as plain text rather than going through csv
+                        let regex = config.regex_delim.as_ref().unwrap();
+                        let joiner = match &config.output_delimiter {
+                            Some(delim) => delim.clone(),
+                            None => char::from(config.delimiter).to_string(),
+                        };
+                        for line in records(buf_reader, terminator) {
+                            let line = line?;
+                            let record: csv::StringRecord = regex.split(&line).collect();
+                            widest = widest.max(record.len());
+                            if record.len() == 1 {
+                                if !config.only_delimited {
+                                    out.write_record(&line)?;
+                                }
+                                continue;
+                            }
+                            let pos = selected(pos, record.len(), config.complement);
+                            out.write_record(
+                                extract_fields(&record, &pos, config.trim).join(&joiner),
+                            )?;
+                        }
+                    }
+                    Extract::Fields(pos) if config.json_names.is_some() => {
+                        // Rows may be ragged; fields they lack become null
                         let mut reader = csv::ReaderBuilder::new()
                             .delimiter(config.delimiter)
                             .has_headers(false)
+                            .flexible(true)
+                            .terminator(csv_terminator(terminator))
                             .from_reader(buf_reader);
+                        let names = config.json_names.as_ref().unwrap();
+                        let mut record = csv::StringRecord::new();
+                        while reader.read_record(&mut record)? {
+                            widest = widest.max(record.len());
+                            if config.only_delimited && record.len() == 1 {
+                                continue;
+                            }
+                            let object = extract_json(&record, pos, names, config.trim);
+                            out.write_record(serde_json::to_vec(&object)?)?;
+                        }
+                    }
+                    Extract::Fields(pos) => {
+                        // Anything but a single byte can't go through the csv
+                        // writer, so such fields are joined as they are
+                        let joiner = config
+                            .output_delimiter
+                            .as_ref()
+                            .filter(|delim| delim.len() != 1);
+                        let out_delimiter = match &config.output_delimiter {
+                            Some(delim) if delim.len() == 1 => delim.as_bytes()[0],
+                            _ => config.delimiter,
+                        };
                         let mut writer = csv::WriterBuilder::new()
-                            .delimiter(config.delimiter)
+                            .delimiter(out_delimiter)
+                            .flexible(true)
+                            .terminator(csv::Terminator::Any(terminator))
                             .from_writer(io::stdout());
-                        for record in reader.records() {
-                            let extracted_fields = extract_fields(&record?, pos);
-                            writer.write_record(extracted_fields)?;
+                        // Reuse single line, parser and record buffers across rows
+                        let mut line = Vec::new();
+                        let mut parser = FieldParser::new(config.delimiter, terminator);
+                        let mut sample = vec![];
+                        let mut splits = false;
+                        loop {
+                            line.clear();
+                            let eof = buf_reader.read_until(terminator, &mut line)? == 0;
+                            let text = line.strip_suffix(&[terminator]).unwrap_or(&line);
+                            // As in GNU cut, a line without the delimiter is
+                            // printed verbatim rather than as a CSV record,
+                            // or skipped with -s. Lines continuing a quoted
+                            // field belong to the record being parsed.
+                            if !eof && !parser.pending && !text.contains(&config.delimiter) {
+                                widest = widest.max(1);
+                                if config.detect_delimiter && sample.len() < DETECT_RECORDS {
+                                    sample.push(String::from_utf8_lossy(text).into_owned());
+                                }
+                                if !config.only_delimited {
+                                    writer.flush()?;
+                                    out.write_record(text)?;
+                                }
+                                continue;
+                            }
+                            parser.feed(&line, |record| {
+                                widest = widest.max(record.len());
+                                if config.detect_delimiter
+                                    && !splits
+                                    && sample.len() < DETECT_RECORDS
+                                {
+                                    match record.len() {
+                                        1 => sample.push(record[0].to_owned()),
+                                        _ => splits = true,
+                                    }
+                                }
+                                let pos = selected(pos, record.len(), config.complement);
+                                let extracted_fields = extract_fields(record, &pos, config.trim);
+                                match joiner {
+                                    Some(joiner) => {
+                                        writer.flush()?;
+                                        out.write_record(extracted_fields.join(joiner))?;
+                                    }
+                                    None => writer.write_record(extracted_fields)?,
+                                }
+                                Ok(())
+                            })?;
+                            if eof {
+                                break;
+                            }
                         }
                         writer.flush()?;
+                        if config.detect_delimiter && !splits {
+                            if let Some(hint) = delimiter_hint(&sample, config.delimiter) {
+                                eprintln!("{}: {}", filename, hint);
+                            }
+                        }
                     }
                 }
             }
         }
     }
+
+    if let (true, Extract::Fields(pos)) = (config.error_on_missing, &config.extract) {
+        if let Some(field) = missing_field(pos, widest) {
+            return Err(From::from(format!(
+                "field {} is missing from every record (the widest has {} fields)",
+                field, widest
+            )));
+        }
+    }
     Ok(())
 }
 
+// Candidates must occur in every sampled record to be suggested
+fn delimiter_hint(sample: &[String], delimiter: u8) -> Option<String> {
+    let found: Vec<_> = CANDIDATE_DELIMITERS
+        .iter()
+        .filter(|(d, _)| *d != delimiter)
+        .filter(|(d, _)| sample.iter().all(|rec| rec.as_bytes().contains(d)))
+        .map(|(_, name)| *name)
+        .collect();
+    if sample.is_empty() || found.is_empty() {
+        return None;
+    }
+    let current = match CANDIDATE_DELIMITERS.iter().find(|(d, _)| *d == delimiter) {
+        Some((_, name)) => name.to_string(),
+        None => format!("\"{}\"", delimiter.escape_ascii()),
+    };
+    Some(format!(
+        "hint: no record was split on {}; try {} instead",
+        current,
+        found.join(" or ")
+    ))
+}
+
 fn parse_pos(ranges: &str) -> MyResult<PositionList> {
     ranges
         .split(',')
         .map(|range| range.split('-').collect())
         .map(|e: Vec<&str>| match e.len() {
-            n if n == 2 => match (parse_positive_int(e[0]), parse_positive_int(e[1])) {
+            // "N-" runs to the end of the line, "-N" starts at the beginning
+            2 if e[1].is_empty() && !e[0].is_empty() => match parse_positive_int(e[0]) {
+                Ok(start) => Ok(Range {
+                    start: start - 1,
+                    end: usize::MAX,
+                }),
+                _ => Err(From::from(format!("illegal list value: \"{}-\"", e[0]))),
+            },
+            2 if e[0].is_empty() && !e[1].is_empty() => match parse_positive_int(e[1]) {
+                Ok(end) => Ok(Range { start: 0, end }),
+                _ => Err(From::from(format!("illegal list value: \"-{}\"", e[1]))),
+            },
+            2 => match (parse_positive_int(e[0]), parse_positive_int(e[1])) {
                 (Ok(start), Ok(end)) if end > start => Ok(Range {
                     start: start - 1,
                     end,
@@ -160,7 +485,7 @@ fn parse_pos(ranges: &str) -> MyResult<PositionList> {
                     e[0], e[1]
                 ))),
             },
-            n if n == 1 => match parse_positive_int(e[0]) {
+            1 => match parse_positive_int(e[0]) {
                 Ok(start) => Ok(Range {
                     start: start - 1,
                     end: start,
@@ -172,6 +497,17 @@ fn parse_pos(ranges: &str) -> MyResult<PositionList> {
         .collect::<Result<Vec<_>, _>>()
 }
 
+// Positions may be separated by commas, newlines or both
+fn read_positions(filename: &str) -> MyResult<String> {
+    let contents = fs::read_to_string(filename).map_err(|e| format!("{}: {}", filename, e))?;
+    Ok(contents
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|pos| !pos.is_empty())
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
 fn parse_positive_int(val: &str) -> MyResult<usize> {
     if !val.chars().all(char::is_numeric) {
         return Err(From::from(val));
@@ -189,40 +525,249 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
+// With --complement the positions depend on each record's length
+fn selected(pos: &[Range<usize>], len: usize, complement: bool) -> Cow<'_, [Range<usize>]> {
+    if complement {
+        Cow::Owned(complement_pos(pos, len))
+    } else {
+        Cow::Borrowed(pos)
+    }
+}
+
+// The positions among the first `len` that `pos` does not select, in input
+// order, with neighbouring positions merged into one range
+fn complement_pos(pos: &[Range<usize>], len: usize) -> PositionList {
+    let mut kept: PositionList = vec![];
+    for i in (0..len).filter(|i| !pos.iter().any(|range| range.contains(i))) {
+        match kept.last_mut() {
+            Some(last) if last.end == i => last.end += 1,
+            _ => kept.push(i..i + 1),
+        }
+    }
+    kept
+}
+
+// Like BufRead::lines, but split on the given terminator; a CR before the
+// terminator is only dropped for newline-terminated records
+fn records<R: BufRead>(mut reader: R, terminator: u8) -> impl Iterator<Item = io::Result<String>> {
+    std::iter::from_fn(move || {
+        let mut buf = Vec::new();
+        match reader.read_until(terminator, &mut buf) {
+            Ok(0) => None,
+            Ok(_) => {
+                if buf.last() == Some(&terminator) {
+                    buf.pop();
+                    if terminator == b'\n' && buf.last() == Some(&b'\r') {
+                        buf.pop();
+                    }
+                }
+                Some(
+                    String::from_utf8(buf)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+                )
+            }
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+// Records end at NUL with -z; otherwise \n, \r and \r\n all end a record,
+// as by default in the csv crate
+fn csv_terminator(terminator: u8) -> csv::Terminator {
+    match terminator {
+        b'\n' => csv::Terminator::CRLF,
+        _ => csv::Terminator::Any(terminator),
+    }
+}
+
+/// Parses delimited records from the lines fed to it, keeping its state
+/// between lines so a quoted field may span several of them.
+struct FieldParser {
+    parser: csv_core::Reader,
+    record: csv::StringRecord,
+    fields: Vec<u8>,
+    ends: Vec<usize>,
+    len: usize,
+    count: usize,
+    /// Whether a record has been started but not yet finished
+    pending: bool,
+}
+
+impl FieldParser {
+    fn new(delimiter: u8, terminator: u8) -> Self {
+        let terminator = match terminator {
+            b'\n' => csv_core::Terminator::CRLF,
+            _ => csv_core::Terminator::Any(terminator),
+        };
+        FieldParser {
+            parser: csv_core::ReaderBuilder::new()
+                .delimiter(delimiter)
+                .terminator(terminator)
+                .build(),
+            record: csv::StringRecord::new(),
+            fields: vec![0; 1024],
+            ends: vec![0; 32],
+            len: 0,
+            count: 0,
+            pending: false,
+        }
+    }
+
+    // Feeds a line, terminator included, or nothing at the end of the input,
+    // and calls `emit` with each record that completes
+    fn feed(
+        &mut self,
+        mut input: &[u8],
+        mut emit: impl FnMut(&csv::StringRecord) -> MyResult<()>,
+    ) -> MyResult<()> {
+        use csv_core::ReadRecordResult::*;
+        let eof = input.is_empty();
+        loop {
+            let (result, nin, nout, nend) = self.parser.read_record(
+                input,
+                &mut self.fields[self.len..],
+                &mut self.ends[self.count..],
+            );
+            input = &input[nin..];
+            self.len += nout;
+            self.count += nend;
+            match result {
+                // The LF of a CRLF may be all that's left, which starts nothing
+                InputEmpty => {
+                    self.pending = self.len > 0 || self.count > 0;
+                    return Ok(());
+                }
+                OutputFull => self.fields.resize(self.fields.len() * 2, 0),
+                OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                Record => {
+                    self.record.clear();
+                    let mut start = 0;
+                    for &end in &self.ends[..self.count] {
+                        self.record
+                            .push_field(std::str::from_utf8(&self.fields[start..end])?);
+                        start = end;
+                    }
+                    self.len = 0;
+                    self.count = 0;
+                    self.pending = false;
+                    emit(&self.record)?;
+                    // An empty input would tell the parser the data has ended
+                    if input.is_empty() && !eof {
+                        return Ok(());
+                    }
+                }
+                End => {
+                    self.pending = false;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+// Open-ended ranges end at usize::MAX, so cut them down to the input's length
+fn clamp(range: &Range<usize>, len: usize) -> Range<usize> {
+    range.start.min(len)..range.end.min(len)
+}
+
 fn extract_chars(line: &str, char_pos: &[Range<usize>]) -> String {
     let chars: Vec<_> = line.chars().collect();
     char_pos
         .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| chars.get(i)))
+        .flat_map(|range| chars[clamp(range, chars.len())].iter())
         .collect()
 }
 
-fn extract_bytes(line: &str, byte_pos: &[Range<usize>]) -> String {
+fn extract_bytes(line: &str, byte_pos: &[Range<usize>], preserve_chars: bool) -> String {
     let bytes = line.as_bytes();
     let extracted: Vec<_> = byte_pos
         .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| bytes.get(i).copied()))
+        .map(|range| clamp(range, bytes.len()))
+        .map(|range| {
+            if preserve_chars {
+                snap_to_chars(line, range)
+            } else {
+                range
+            }
+        })
+        .flat_map(|range| bytes[range].iter().copied())
         .collect();
     String::from_utf8_lossy(&extracted).into_owned()
 }
 
-fn extract_fields(record: &csv::StringRecord, field_pos: &[Range<usize>]) -> Vec<String> {
+// Moves the start back and the end forward to the nearest character
+// boundaries, so a range touching any byte of a character takes all of it
+fn snap_to_chars(line: &str, range: Range<usize>) -> Range<usize> {
+    let mut start = range.start;
+    while !line.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = range.end.max(start);
+    while !line.is_char_boundary(end) {
+        end += 1;
+    }
+    start..end
+}
+
+// Fields are emitted in the order the positions were given, including
+// repeats, so "3,1,1" yields the third field followed by the first twice
+fn extract_fields(
+    record: &csv::StringRecord,
+    field_pos: &[Range<usize>],
+    trim: bool,
+) -> Vec<String> {
+    field_pos
+        .iter()
+        .flat_map(|range| clamp(range, record.len()).filter_map(|i| record.get(i)))
+        .map(|field| if trim { field.trim() } else { field }.to_owned())
+        .collect()
+}
+
+// The first selected field, numbered from 1, that lies past the end of
+// even the widest record; an open range counts as selecting its start
+fn missing_field(field_pos: &[Range<usize>], widest: usize) -> Option<usize> {
+    field_pos
+        .iter()
+        .find(|range| range.start >= widest)
+        .map(|range| range.start + 1)
+}
+
+// Pairs each selected position with its name, in selection order; positions
+// past the end of the record map to null
+fn extract_json(
+    record: &csv::StringRecord,
+    field_pos: &[Range<usize>],
+    names: &[String],
+    trim: bool,
+) -> serde_json::Map<String, serde_json::Value> {
     field_pos
         .iter()
         .cloned()
-        .flat_map(|range| range.filter_map(|i| record.get(i)))
-        .map(|field| field.to_owned())
+        .flatten()
+        .zip(names)
+        .map(|(i, name)| {
+            let value = match record.get(i) {
+                Some(field) => serde_json::Value::from(if trim { field.trim() } else { field }),
+                None => serde_json::Value::Null,
+            };
+            (name.clone(), value)
+        })
         .collect()
 }
 
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod unit_tests {
+    use super::complement_pos;
+    use super::delimiter_hint;
     use super::extract_bytes;
     use super::extract_chars;
     use super::extract_fields;
+    use super::extract_json;
+    use super::missing_field;
     use super::parse_pos;
+    use super::read_positions;
+    use super::PositionList;
 
     #[test]
     fn test_parse_pos() {
@@ -273,9 +818,17 @@ mod unit_tests {
         let res = parse_pos("1,");
         assert!(res.is_err());
 
-        let res = parse_pos("1-");
+        let res = parse_pos("1--");
         assert!(res.is_err());
 
+        let res = parse_pos("a-");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"a-\"",);
+
+        let res = parse_pos("-0");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"-0\"",);
+
         let res = parse_pos("1-1-1");
         assert!(res.is_err());
 
@@ -327,7 +880,27 @@ mod unit_tests {
         let res = parse_pos("15,19-20");
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), vec![14..15, 18..20]);
+
+        let res = parse_pos("2-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![1..usize::MAX]);
+
+        let res = parse_pos("-3");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..3]);
+
+        let res = parse_pos("1,4-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..1, 3..usize::MAX]);
+    }
+    #[test]
+    fn test_read_positions() {
+        let res = read_positions("tests/inputs/positions.txt");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), "3,1,2");
+        assert_eq!(parse_pos("3,1,2").unwrap(), vec![2..3, 0..1, 1..2]);
     }
+
     #[test]
     fn test_extract_chars() {
         assert_eq!(extract_chars("", &[0..1]), "".to_string());
@@ -336,25 +909,121 @@ mod unit_tests {
         assert_eq!(extract_chars("ábc", &[0..3]), "ábc".to_string());
         assert_eq!(extract_chars("ábc", &[2..3, 1..2]), "cb".to_string());
         assert_eq!(extract_chars("ábc", &[0..1, 1..2, 4..5]), "áb".to_string());
+        assert_eq!(extract_chars("ábc", &[1..usize::MAX]), "bc".to_string());
+        assert_eq!(extract_chars("ábc", &[5..usize::MAX]), "".to_string());
     }
 
     #[test]
     fn test_extract_bytes() {
-        assert_eq!(extract_bytes("ábc", &[0..1]), "�".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..2]), "á".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..3]), "áb".to_string());
-        assert_eq!(extract_bytes("ábc", &[0..4]), "ábc".to_string());
-        assert_eq!(extract_bytes("ábc", &[3..4, 2..3]), "cb".to_string());
-        assert_eq!(extract_bytes("ábc", &[3..4, 2..3]), "cb".to_string());
+        assert_eq!(extract_bytes("ábc", &[0..1], false), "�".to_string());
+        assert_eq!(extract_bytes("ábc", &[0..2], false), "á".to_string());
+        assert_eq!(extract_bytes("ábc", &[0..3], false), "áb".to_string());
+        assert_eq!(extract_bytes("ábc", &[0..4], false), "ábc".to_string());
+        assert_eq!(extract_bytes("ábc", &[3..4, 2..3], false), "cb".to_string());
+        assert_eq!(extract_bytes("ábc", &[3..4, 2..3], false), "cb".to_string());
+        assert_eq!(
+            extract_bytes("ábc", &[2..usize::MAX], false),
+            "bc".to_string()
+        );
+    }
+
+    #[test]
+    fn test_extract_bytes_preserve_chars() {
+        assert_eq!(extract_bytes("ábc", &[0..1], true), "á".to_string());
+        assert_eq!(extract_bytes("ábc", &[1..2], true), "á".to_string());
+        assert_eq!(extract_bytes("ábc", &[1..3], true), "áb".to_string());
+        assert_eq!(extract_bytes("ábc", &[2..3], true), "b".to_string());
+        assert_eq!(extract_bytes("ábc", &[3..4, 0..1], true), "cá".to_string());
+        assert_eq!(
+            extract_bytes("ábc", &[1..usize::MAX], true),
+            "ábc".to_string()
+        );
     }
 
     #[test]
     fn test_extract_fields() {
         let rec = csv::StringRecord::from(vec!["Captain", "Sham", "12345"]);
-        assert_eq!(extract_fields(&rec, &[0..1]), &["Captain"]);
-        assert_eq!(extract_fields(&rec, &[1..2]), &["Sham"]);
-        assert_eq!(extract_fields(&rec, &[0..1, 2..3]), &["Captain", "12345"]);
-        assert_eq!(extract_fields(&rec, &[0..1, 3..4]), &["Captain"]);
-        assert_eq!(extract_fields(&rec, &[1..2, 0..1]), &["Sham", "Captain"]);
+        assert_eq!(extract_fields(&rec, &[0..1], false), &["Captain"]);
+        assert_eq!(extract_fields(&rec, &[1..2], false), &["Sham"]);
+        assert_eq!(
+            extract_fields(&rec, &[0..1, 2..3], false),
+            &["Captain", "12345"]
+        );
+        assert_eq!(extract_fields(&rec, &[0..1, 3..4], false), &["Captain"]);
+        assert_eq!(
+            extract_fields(&rec, &[1..usize::MAX], false),
+            &["Sham", "12345"]
+        );
+        assert_eq!(
+            extract_fields(&rec, &[1..2, 0..1], false),
+            &["Sham", "Captain"]
+        );
+        assert_eq!(
+            extract_fields(&rec, &[2..3, 0..1, 0..1], false),
+            &["12345", "Captain", "Captain"]
+        );
+
+        let rec = csv::StringRecord::from(vec![" Captain", "Sham ", "\t12345 "]);
+        assert_eq!(
+            extract_fields(&rec, &[0..3], false),
+            &[" Captain", "Sham ", "\t12345 "]
+        );
+        assert_eq!(
+            extract_fields(&rec, &[0..3], true),
+            &["Captain", "Sham", "12345"]
+        );
+    }
+
+    #[test]
+    fn test_extract_json() {
+        let rec = csv::StringRecord::from(vec!["Captain", " Sham "]);
+        let names = ["b".to_string(), "a".to_string(), "c".to_string()];
+        let json =
+            |trim| serde_json::Value::from(extract_json(&rec, &[1..2, 0..1, 5..6], &names, trim));
+        assert_eq!(
+            json(false),
+            serde_json::json!({"b": " Sham ", "a": "Captain", "c": null})
+        );
+        assert_eq!(json(true)["b"], "Sham");
+        assert_eq!(
+            json(false).to_string(),
+            r#"{"b":" Sham ","a":"Captain","c":null}"#
+        );
+    }
+
+    #[test]
+    fn test_complement_pos() {
+        assert_eq!(complement_pos(&[1..2, 3..4], 5), vec![0..1, 2..3, 4..5]);
+        assert_eq!(complement_pos(&[3..4, 0..1], 6), vec![1..3, 4..6]);
+        assert_eq!(complement_pos(&[1..usize::MAX], 4), vec![0..1]);
+        assert_eq!(complement_pos(&[0..2], 2), PositionList::new());
+        assert_eq!(complement_pos(&[5..6], 3), vec![0..3]);
+        assert_eq!(complement_pos(&[0..1], 0), PositionList::new());
+    }
+
+    #[test]
+    fn test_delimiter_hint() {
+        let sample = vec!["a\tb".to_string(), "c\td;e".to_string()];
+        assert_eq!(
+            delimiter_hint(&sample, b','),
+            Some("hint: no record was split on comma; try tab instead".to_string())
+        );
+        assert_eq!(delimiter_hint(&sample, b'\t'), None);
+        assert_eq!(delimiter_hint(&[], b','), None);
+
+        let sample = vec!["a;b|c".to_string()];
+        assert_eq!(
+            delimiter_hint(&sample, b':'),
+            Some("hint: no record was split on \":\"; try semicolon or pipe instead".to_string())
+        );
+    }
+
+    #[test]
+    fn test_missing_field() {
+        assert_eq!(missing_field(&[0..1, 2..3], 3), None);
+        assert_eq!(missing_field(&[0..1, 8..9], 3), Some(9));
+        assert_eq!(missing_field(&[3..usize::MAX], 3), Some(4));
+        assert_eq!(missing_field(&[0..usize::MAX], 1), None);
+        assert_eq!(missing_field(&[0..1], 0), Some(1));
     }
 }
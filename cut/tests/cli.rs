@@ -9,6 +9,8 @@ const PRG: &str = "cut";
 const CSV: &str = "tests/inputs/movies1.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const PADDED: &str = "tests/inputs/padded.csv";
+const POSITIONS: &str = "tests/inputs/positions.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -35,7 +37,7 @@ fn skips_bad_file() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&["-f", "1", CSV, &bad, TSV])
+        .args(["-f", "1", CSV, &bad, TSV])
         .assert()
         .success()
         .stderr(predicate::str::is_match(expected)?);
@@ -110,7 +112,7 @@ fn dies_bad_delimiter() -> TestResult {
 #[test]
 fn dies_chars_bytes_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-f", "1", "-b", "1"])
+        .args([CSV, "-c", "1", "-f", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -120,7 +122,7 @@ fn dies_chars_bytes_fields() -> TestResult {
 #[test]
 fn dies_bytes_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-f", "1", "-b", "1"])
+        .args([CSV, "-f", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -130,7 +132,7 @@ fn dies_bytes_fields() -> TestResult {
 #[test]
 fn dies_chars_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-f", "1"])
+        .args([CSV, "-c", "1", "-f", "1"])
         .assert()
         .failure();
     Ok(())
@@ -140,7 +142,7 @@ fn dies_chars_fields() -> TestResult {
 #[test]
 fn dies_chars_bytes() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-b", "1"])
+        .args([CSV, "-c", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -296,6 +298,35 @@ fn tsv_b1_8() -> TestResult {
     run_lossy(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
 }
 
+// --------------------------------------------------
+#[test]
+fn preserve_chars() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-b", "1", "--preserve-chars"])
+        .write_stdin("ábc\nxyz\n")
+        .assert()
+        .success()
+        .stdout("á\nx\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-b", "1"])
+        .write_stdin("ábc\n")
+        .assert()
+        .success()
+        .stdout("\u{FFFD}\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_preserve_chars_without_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([TSV, "-c", "1", "--preserve-chars"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn tsv_c1() -> TestResult {
@@ -337,3 +368,472 @@ fn tsv_c1_8() -> TestResult {
 fn repeated_value() -> TestResult {
     run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn padded_f1_3() -> TestResult {
+    run(
+        &[PADDED, "-f", "1,3", "-d", ","],
+        "tests/expected/padded.csv.f1,3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn padded_f1_3_trim() -> TestResult {
+    run(
+        &[PADDED, "-f", "1,3", "-d", ",", "--trim"],
+        "tests/expected/padded.csv.f1,3.trim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_trim_without_fields() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([PADDED, "-c", "1", "--trim"])
+        .assert()
+        .failure()
+        .stderr("--trim needs --fields, --fields-file or --template\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn padded_template_trim() -> TestResult {
+    run(
+        &[PADDED, "--template", "1,3", "-d", ",", "--trim"],
+        "tests/expected/padded.csv.f1,3.trim.out",
+    )?;
+
+    Command::cargo_bin(PRG)?
+        .args([PADDED, "--template", "3,1", "-d", ",", "--trim"])
+        .assert()
+        .success()
+        .stdout(
+            "director,name\n\
+             John Landis,The Blues Brothers\n\
+             Tom Hooper,Les Misérables\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_fields_file() -> TestResult {
+    run(
+        &[CSV, "--fields-file", POSITIONS, "-d", ","],
+        "tests/expected/movies1.csv.fields-file.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_template_3_1_1() -> TestResult {
+    run(
+        &[CSV, "--template", "3,1,1", "-d", ","],
+        "tests/expected/movies1.csv.template3_1_1.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_template_and_fields() -> TestResult {
+    dies(
+        &[CSV, "--template", "3,1", "-f", "1"],
+        "cannot be used with",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_json_f3_1_4() -> TestResult {
+    run(
+        &[
+            CSV,
+            "--json",
+            "--field-names",
+            "director,title,x",
+            "-f",
+            "3,1,4",
+            "-d",
+            ",",
+        ],
+        "tests/expected/movies1.csv.json.f3_1_4.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn json_ragged_rows() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--json", "--field-names", "one,two", "-f", "1-2", "-d", ","])
+        .write_stdin("a,b\nc\n")
+        .assert()
+        .success()
+        .stdout("{\"one\":\"a\",\"two\":\"b\"}\n{\"one\":\"c\",\"two\":null}\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn open_ranges() -> TestResult {
+    for (args, expected) in [
+        (
+            vec!["-d", ",", "-f", "-2", CSV],
+            "title,year\nThe Blues Brothers,1980\nLes Misérables,2012\n",
+        ),
+        (
+            vec!["-d", ",", "-f", "2-", CSV],
+            "year,director\n1980,John Landis\n2012,Tom Hooper\n",
+        ),
+        (
+            vec!["-d", ",", "-f", "1,3-", CSV],
+            "title,director\nThe Blues Brothers,John Landis\nLes Misérables,Tom Hooper\n",
+        ),
+        (vec!["-c", "-3", CSV], "tit\nThe\nLes\n"),
+        (
+            vec!["-c", "12-", CSV],
+            "director\nrothers,1980,John Landis\nles,2012,Tom Hooper\n",
+        ),
+        (vec!["-b", "99-", CSV], "\n\n\n"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn complement() -> TestResult {
+    for (args, expected) in [
+        (
+            vec!["-d", ",", "-f", "2", "--complement", CSV],
+            "title,director\nThe Blues Brothers,John Landis\nLes Misérables,Tom Hooper\n",
+        ),
+        // Remaining fields keep their input order
+        (
+            vec!["-d", ",", "-f", "3,1", "--complement", CSV],
+            "year\n1980\n2012\n",
+        ),
+        (vec!["-b", "2-", "--complement", CSV], "t\nT\nL\n"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn complement_wide_chars() -> TestResult {
+    // é is one character but two bytes
+    for (args, expected) in [
+        (["-c", "2", "--complement"], "hllo\n"),
+        (["-b", "2-3", "--complement"], "hllo\n"),
+        (["-c", "1,3", "--complement"], "élo\n"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .write_stdin("héllo\n")
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_json_open_range() -> TestResult {
+    dies(
+        &[CSV, "--json", "--field-names", "a", "-f", "2-"],
+        "--field-names cannot name an open-ended range",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_json_name_count() -> TestResult {
+    dies(
+        &[CSV, "--json", "--field-names", "a", "-f", "1-2"],
+        "--field-names has 1 names but 2 fields are selected",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn warns_delim_without_fields() -> TestResult {
+    let warning = "warning: --delim is only used with --fields";
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-c", "1", CSV])
+        .assert()
+        .success()
+        .stdout("t\nT\nL\n")
+        .stderr(predicate::str::contains(warning));
+
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "1", CSV])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(warning).not());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_delimiter_line_verbatim() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "2"])
+        .write_stdin("a,b\n\"quoted\" \"line\"\n\nc,d\n")
+        .assert()
+        .success()
+        .stdout("b\n\"quoted\" \"line\"\n\nd\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_line_endings() -> TestResult {
+    // The CR belongs to the line ending, not to the last field
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "2"])
+        .write_stdin("a,b\r\nc,\"d\"\r\n")
+        .assert()
+        .success()
+        .stdout("b\nd\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "1,2", "--json", "--field-names", "x,y"])
+        .write_stdin("a,b\r\n")
+        .assert()
+        .success()
+        .stdout("{\"x\":\"a\",\"y\":\"b\"}\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quoted_field_spans_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "2"])
+        .write_stdin("a,\"b\n\nc,d\",e\nf,g\nplain\n")
+        .assert()
+        .success()
+        .stdout("\"b\n\nc,d\"\ng\nplain\n");
+
+    // The last record needn't end with a newline
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "3"])
+        .write_stdin("a,\"b\nc\",d")
+        .assert()
+        .success()
+        .stdout("d\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn only_delimited() -> TestResult {
+    let input = "a,b\nno delimiter\n\nc,d\n";
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "2", "-s"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("b\nd\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "2", "--only-delimited", "--json"])
+        .args(["--field-names", "second"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("{\"second\":\"b\"}\n{\"second\":\"d\"}\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn output_delimiter() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([BOOKS, "-f", "1,3", "--output-delimiter", ","])
+        .assert()
+        .success()
+        .stdout(concat!(
+            "Author,Title\n",
+            "Émile Zola,La Confession de Claude\n",
+            "Samuel Beckett,Waiting for Godot\n",
+            "Jules Verne,\"20,000 Leagues Under the Sea\"\n",
+        ));
+
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "1-", "--output-delimiter", " | "])
+        .write_stdin("a,b,c\nno delimiter\nd,\"e, f\"\nragged,row,with,more\n")
+        .assert()
+        .success()
+        .stdout("a | b | c\nno delimiter\nd | e, f\nragged | row | with | more\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_output_delimiter_with_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([BOOKS, "-b", "1", "--output-delimiter", ","])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_terminated() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-c", "1-3"])
+        .write_stdin("first\nname\0second\0third")
+        .assert()
+        .success()
+        .stdout("fir\0sec\0thi\0");
+
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-d", ",", "-f", "2"])
+        .write_stdin("a,multi\nline\0no delimiter\0b,c\0")
+        .assert()
+        .success()
+        .stdout("multi\nline\0no delimiter\0c\0");
+
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-d", ",", "-f", "1", "--json", "--field-names", "x"])
+        .write_stdin("a,b\0c\nd,e\0")
+        .assert()
+        .success()
+        .stdout("{\"x\":\"a\"}\0{\"x\":\"c\\nd\"}\0");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn error_on_missing() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([CSV, "-d", ",", "-f", "1,9", "--error-on-missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "field 9 is missing from every record (the widest has 3 fields)",
+        ));
+
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "1,3", "--error-on-missing"])
+        .write_stdin("a,b\nc,d,e\n")
+        .assert()
+        .success()
+        .stdout("a\nc,e\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_delim() -> TestResult {
+    let input = "INFO | boot | ok\nno separator\nWARN  |disk|  \"full\"\n";
+    Command::cargo_bin(PRG)?
+        .args(["--regex-delim", r"\s*\|\s*", "-f", "3,1"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("ok\tINFO\nno separator\n\"full\"\tWARN\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["--regex-delim", r" \| ", "-f", "2-", "-s"])
+        .args(["--output-delimiter", " | "])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("boot | ok\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_regex_delim() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([CSV, "--regex-delim", "(", "-f", "1"])
+        .assert()
+        .failure()
+        .stderr("Invalid --regex-delim \"(\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn detect_delimiter() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-d", ",", "-f", "1", "--detect-delimiter", TSV])
+        .assert()
+        .success()
+        .stderr(format!(
+            "{}: hint: no record was split on comma; try tab instead\n",
+            TSV
+        ));
+
+    // No hint once the delimiter actually splits the data
+    Command::cargo_bin(PRG)?
+        .args(["-f", "1", "--detect-delimiter", TSV])
+        .assert()
+        .success()
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_fields_file() -> TestResult {
+    let bad = gen_bad_file();
+    dies(
+        &[CSV, "--fields-file", &bad],
+        &format!("{}: No such file or directory", bad),
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_fields_file_and_fields() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([CSV, "--fields-file", POSITIONS, "-f", "1"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_many_records() -> TestResult {
+    let mut input = String::new();
+    let mut expected = String::new();
+    for i in 0..5000 {
+        // Vary field widths and quoting so a reused record buffer must be
+        // fully reset between rows
+        let name = "x".repeat(i % 17);
+        input.push_str(&format!("{},\"{}, {}\",{}\n", i, name, i % 3, i * 7));
+        expected.push_str(&format!("\"{}, {}\",{}\n", name, i % 3, i));
+    }
+
+    let filename = std::env::temp_dir().join(format!("cut-{}.csv", random_string()));
+    fs::write(&filename, &input)?;
+    let output = Command::cargo_bin(PRG)?
+        .args(["-f", "2,1", "-d", ","])
+        .arg(&filename)
+        .output();
+    fs::remove_file(&filename)?;
+
+    let stdout = String::from_utf8(output?.stdout)?;
+    assert_eq!(stdout, expected);
+    Ok(())
+}
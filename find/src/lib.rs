@@ -1,8 +1,14 @@
 use clap::{App, Arg};
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::fs::{self, FileType, Metadata};
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::process::Command;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use walkdir::{DirEntry, WalkDir};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
@@ -12,13 +18,35 @@ enum EntryType {
     Dir,
     File,
     Link,
+    Socket,
+    Fifo,
+    Block,
+    Char,
+}
+
+/// A piece of a --printf format.
+#[derive(Debug, Eq, PartialEq)]
+enum Directive {
+    Literal(String),
+    Path,
+    Name,
+    Size,
+    Type,
 }
 
 #[derive(Debug)]
 pub struct Config {
     paths: Vec<String>,
     names: Vec<Regex>,
+    path_patterns: Vec<Regex>,
     entry_types: Vec<EntryType>,
+    newer_than: Option<SystemTime>,
+    long: bool,
+    delete: bool,
+    yes: bool,
+    exec_dir: Option<Vec<String>>,
+    max_results: Option<usize>,
+    printf: Option<Vec<Directive>>,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -41,15 +69,79 @@ pub fn get_args() -> MyResult<Config> {
                 .multiple(true)
                 .help("Name"),
         )
+        .arg(
+            Arg::with_name("path")
+                .long("path")
+                .value_name("PATTERN")
+                .multiple(true)
+                .help("Whole path"),
+        )
+        .arg(
+            Arg::with_name("ipath")
+                .long("ipath")
+                .value_name("PATTERN")
+                .multiple(true)
+                .help("Whole path, case-insensitive"),
+        )
         .arg(
             Arg::with_name("types")
                 .short("t")
                 .long("type")
                 .value_name("TYPE")
                 .multiple(true)
-                .possible_values(&["f", "d", "l"])
+                .possible_values(&["f", "d", "l", "s", "p", "b", "c"])
                 .help("Entry type"),
         )
+        .arg(
+            Arg::with_name("newermt")
+                .long("newermt")
+                .value_name("TIMESTAMP")
+                .help("Modified after TIMESTAMP (YYYY-MM-DD or RFC 3339)"),
+        )
+        .arg(
+            Arg::with_name("long")
+                .long("long")
+                .alias("ls")
+                .takes_value(false)
+                .help("List permissions, size and modification time"),
+        )
+        .arg(
+            Arg::with_name("delete")
+                .long("delete")
+                .takes_value(false)
+                .conflicts_with("long")
+                .help("Delete matches, depth-first (dry run unless --yes)"),
+        )
+        .arg(
+            Arg::with_name("yes")
+                .long("yes")
+                .takes_value(false)
+                .requires("delete")
+                .help("Really delete with --delete"),
+        )
+        .arg(
+            Arg::with_name("exec_dir")
+                .long("exec-dir")
+                .value_name("COMMAND")
+                .multiple(true)
+                .allow_hyphen_values(true)
+                .value_terminator(";")
+                .conflicts_with_all(&["delete", "long"])
+                .help("Run COMMAND ... ; from each match's directory, {} being ./BASENAME"),
+        )
+        .arg(
+            Arg::with_name("printf")
+                .long("printf")
+                .value_name("FORMAT")
+                .conflicts_with_all(&["delete", "long", "exec_dir"])
+                .help("Print FORMAT per match: %p path, %f name, %s size, %y type"),
+        )
+        .arg(
+            Arg::with_name("max_results")
+                .long("max-results")
+                .value_name("N")
+                .help("Stop after acting on N matches"),
+        )
         .get_matches();
 
     Ok(Config {
@@ -63,20 +155,55 @@ pub fn get_args() -> MyResult<Config> {
             .unwrap_or_default()
             .map(|n| Regex::new(n).map_err(|_| format!("Invalid --name \"{}\"", n)))
             .collect::<Result<Vec<_>, _>>()?,
+        path_patterns: ["path", "ipath"]
+            .iter()
+            .flat_map(|&arg| {
+                matches
+                    .values_of(arg)
+                    .unwrap_or_default()
+                    .map(move |p| (arg, p))
+            })
+            .map(|(arg, p)| {
+                RegexBuilder::new(p)
+                    .case_insensitive(arg == "ipath")
+                    .build()
+                    .map_err(|_| format!("Invalid --{} \"{}\"", arg, p))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
         entry_types: matches
             .values_of("types")
             .unwrap_or_default()
-            .map(|t| EntryType::from_str(t))
+            .map(EntryType::from_str)
             .collect::<Result<Vec<_>, _>>()?,
+        newer_than: matches
+            .value_of("newermt")
+            .map(|t| parse_timestamp(t).map_err(|_| format!("Invalid --newermt \"{}\"", t)))
+            .transpose()?,
+        long: matches.is_present("long"),
+        delete: matches.is_present("delete"),
+        yes: matches.is_present("yes"),
+        exec_dir: matches.values_of_lossy("exec_dir"),
+        max_results: matches
+            .value_of("max_results")
+            .map(|n| {
+                n.parse()
+                    .map_err(|_| format!("Invalid --max-results \"{}\"", n))
+            })
+            .transpose()?,
+        printf: matches
+            .value_of("printf")
+            .map(|f| parse_printf(f).map_err(|e| format!("Invalid --printf \"{}\": {}", f, e)))
+            .transpose()?,
     })
 }
 
-fn filter_type(entry: DirEntry, entry_types: &Vec<EntryType>) -> Option<DirEntry> {
+fn filter_type(entry: DirEntry, entry_types: &[EntryType]) -> Option<DirEntry> {
     if entry_types.is_empty()
         || entry_types.iter().any(|entry_type| match entry_type {
             EntryType::Dir => entry.file_type().is_dir(),
             EntryType::File => entry.file_type().is_file(),
             EntryType::Link => entry.file_type().is_symlink(),
+            special => is_special_type(entry.file_type(), special),
         })
     {
         Some(entry)
@@ -85,7 +212,24 @@ fn filter_type(entry: DirEntry, entry_types: &Vec<EntryType>) -> Option<DirEntry
     }
 }
 
-fn filter_name(entry: DirEntry, name: &Vec<Regex>) -> Option<DirEntry> {
+#[cfg(unix)]
+fn is_special_type(file_type: FileType, entry_type: &EntryType) -> bool {
+    match entry_type {
+        EntryType::Socket => file_type.is_socket(),
+        EntryType::Fifo => file_type.is_fifo(),
+        EntryType::Block => file_type.is_block_device(),
+        EntryType::Char => file_type.is_char_device(),
+        _ => false,
+    }
+}
+
+// Sockets, pipes and devices only exist on Unix, so they never match elsewhere
+#[cfg(not(unix))]
+fn is_special_type(_file_type: FileType, _entry_type: &EntryType) -> bool {
+    false
+}
+
+fn filter_name(entry: DirEntry, name: &[Regex]) -> Option<DirEntry> {
     if name.is_empty()
         || name
             .iter()
@@ -97,15 +241,59 @@ fn filter_name(entry: DirEntry, name: &Vec<Regex>) -> Option<DirEntry> {
     }
 }
 
+fn filter_path(entry: DirEntry, path_patterns: &[Regex]) -> Option<DirEntry> {
+    if path_patterns.is_empty()
+        || path_patterns
+            .iter()
+            .any(|regex| regex.is_match(&entry.path().to_string_lossy()))
+    {
+        Some(entry)
+    } else {
+        None
+    }
+}
+
+fn filter_newer(entry: DirEntry, newer_than: &Option<SystemTime>) -> Option<DirEntry> {
+    match newer_than {
+        None => Some(entry),
+        Some(time) => match entry.metadata().ok().and_then(|m| m.modified().ok()) {
+            Some(modified) if modified > *time => Some(entry),
+            _ => None,
+        },
+    }
+}
+
 pub fn run(config: Config) -> MyResult<()> {
-    for path in config.paths {
-        for entry in WalkDir::new(path) {
+    let mut remaining = config.max_results.unwrap_or(usize::MAX);
+    if remaining == 0 {
+        return Ok(());
+    }
+    'paths: for path in config.paths {
+        // Deleting needs a directory's contents removed before the directory
+        for entry in WalkDir::new(path).contents_first(config.delete) {
             match entry {
                 Ok(entry) => {
                     if let Some(entry) = filter_type(entry, &config.entry_types)
                         .and_then(|entry| filter_name(entry, &config.names))
+                        .and_then(|entry| filter_path(entry, &config.path_patterns))
+                        .and_then(|entry| filter_newer(entry, &config.newer_than))
                     {
-                        println!("{}", entry.path().display())
+                        if let Some(command) = &config.exec_dir {
+                            exec_dir(&entry, command);
+                        } else if config.delete {
+                            delete(&entry, config.yes);
+                        } else if let Some(format) = &config.printf {
+                            print!("{}", format_printf(&entry, format))
+                        } else if config.long {
+                            println!("{}", format_long(&entry))
+                        } else {
+                            println!("{}", entry.path().display())
+                        }
+                        // Stop walking as soon as the last match is acted on
+                        remaining -= 1;
+                        if remaining == 0 {
+                            break 'paths;
+                        }
                     }
                 }
                 Err(e) => eprintln!("{}", e),
@@ -115,6 +303,245 @@ pub fn run(config: Config) -> MyResult<()> {
     Ok(())
 }
 
+// Runs the command from the entry's parent directory with every "{}" replaced
+// by "./BASENAME", so the full path never reaches the command line
+fn exec_dir(entry: &DirEntry, command: &[String]) {
+    let path = entry.path();
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = format!("./{}", entry.file_name().to_string_lossy());
+    let args = command[1..].iter().map(|arg| arg.replace("{}", &name));
+
+    if let Err(e) = Command::new(&command[0])
+        .args(args)
+        .current_dir(dir)
+        .status()
+    {
+        eprintln!("{}: {}", command[0], e);
+    }
+}
+
+// Only prints what would be removed unless confirmed; directories are only
+// removed once empty
+fn delete(entry: &DirEntry, confirmed: bool) {
+    let path = entry.path();
+    if !confirmed {
+        println!("would delete {}", path.display());
+        return;
+    }
+    let res = if entry.file_type().is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    };
+    if let Err(e) = res {
+        eprintln!("{}: {}", path.display(), e);
+    }
+}
+
+// Falls back to the bare path when metadata can't be read
+fn format_long(entry: &DirEntry) -> String {
+    match entry.metadata() {
+        Ok(metadata) => format!(
+            "{} {:>8} {} {}",
+            format_mode(&metadata),
+            metadata.len(),
+            metadata
+                .modified()
+                .map(format_time)
+                .unwrap_or_else(|_| "????-??-?? ??:??".to_string()),
+            entry.path().display()
+        ),
+        Err(_) => entry.path().display().to_string(),
+    }
+}
+
+// Supports GNU find's %p, %f, %s, %y and %%, plus \n, \t and \\ escapes
+fn parse_printf(format: &str) -> MyResult<Vec<Directive>> {
+    let mut directives = vec![];
+    let mut literal = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                let directive = match chars.next() {
+                    Some('%') => {
+                        literal.push('%');
+                        continue;
+                    }
+                    Some('p') => Directive::Path,
+                    Some('f') => Directive::Name,
+                    Some('s') => Directive::Size,
+                    Some('y') => Directive::Type,
+                    Some(other) => return Err(From::from(format!("unknown directive %{}", other))),
+                    None => return Err(From::from("trailing %")),
+                };
+                if !literal.is_empty() {
+                    directives.push(Directive::Literal(std::mem::take(&mut literal)));
+                }
+                directives.push(directive);
+            }
+            '\\' => match chars.next() {
+                Some('n') => literal.push('\n'),
+                Some('t') => literal.push('\t'),
+                Some('\\') => literal.push('\\'),
+                Some(other) => return Err(From::from(format!("unknown escape \\{}", other))),
+                None => return Err(From::from("trailing \\")),
+            },
+            _ => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        directives.push(Directive::Literal(literal));
+    }
+    Ok(directives)
+}
+
+fn format_printf(entry: &DirEntry, format: &[Directive]) -> String {
+    format
+        .iter()
+        .map(|directive| match directive {
+            Directive::Literal(text) => text.to_string(),
+            Directive::Path => entry.path().display().to_string(),
+            Directive::Name => entry.file_name().to_string_lossy().into_owned(),
+            Directive::Size => entry.metadata().map_or(0, |m| m.len()).to_string(),
+            // Unlike --long, GNU find reports regular files as "f"
+            Directive::Type => match type_char(entry.file_type()) {
+                '-' => 'f',
+                c => c,
+            }
+            .to_string(),
+        })
+        .collect()
+}
+
+fn type_char(file_type: FileType) -> char {
+    if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if is_special_type(file_type, &EntryType::Fifo) {
+        'p'
+    } else if is_special_type(file_type, &EntryType::Socket) {
+        's'
+    } else if is_special_type(file_type, &EntryType::Block) {
+        'b'
+    } else if is_special_type(file_type, &EntryType::Char) {
+        'c'
+    } else {
+        '-'
+    }
+}
+
+#[cfg(unix)]
+fn format_mode(metadata: &Metadata) -> String {
+    let mode = metadata.permissions().mode();
+    let mut out = type_char(metadata.file_type()).to_string();
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        out.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        out.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    out
+}
+
+#[cfg(not(unix))]
+fn format_mode(metadata: &Metadata) -> String {
+    let perms = if metadata.permissions().readonly() {
+        "r--r--r--"
+    } else {
+        "rw-rw-rw-"
+    };
+    format!("{}{}", type_char(metadata.file_type()), perms)
+}
+
+/// Formats a timestamp as "YYYY-MM-DD HH:MM" in UTC.
+fn format_time(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let rem = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60
+    )
+}
+
+// Converts days since 1970-01-01 to a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Converts a (year, month, day) civil date to days since 1970-01-01
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parses "YYYY-MM-DD" (midnight UTC) or an RFC 3339 timestamp such as
+/// "2024-01-01T12:30:00+02:00".
+fn parse_timestamp(value: &str) -> MyResult<SystemTime> {
+    let re = Regex::new(
+        r"^(\d{4})-(\d{2})-(\d{2})(?:[Tt ](\d{2}):(\d{2}):(\d{2})(?:\.(\d{1,9}))?([Zz]|([+-])(\d{2}):(\d{2})))?$",
+    )
+    .unwrap();
+    let caps = re.captures(value).ok_or(value)?;
+    let num = |i: usize| -> i64 { caps.get(i).map_or(0, |m| m.as_str().parse().unwrap()) };
+
+    let (year, month, day) = (num(1), num(2), num(3));
+    let (hour, minute, second) = (num(4), num(5), num(6));
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || civil_from_days(days_from_civil(year, month, day)) != (year, month, day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return Err(From::from(value));
+    }
+
+    let offset = match caps.get(9).map(|m| m.as_str()) {
+        Some("-") => -(num(10) * 3600 + num(11) * 60),
+        Some(_) => num(10) * 3600 + num(11) * 60,
+        None => 0,
+    };
+    let secs =
+        days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    let nanos = caps
+        .get(7)
+        .map_or(0, |m| format!("{:0<9}", m.as_str()).parse().unwrap());
+
+    let time = if secs >= 0 {
+        UNIX_EPOCH + Duration::from_secs(secs as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+    };
+    Ok(time + Duration::from_nanos(nanos))
+}
+
 #[derive(Debug, Clone)]
 struct EntryTypeError {
     entry_type: String,
@@ -136,9 +563,108 @@ impl FromStr for EntryType {
             "f" => Ok(EntryType::File),
             "d" => Ok(EntryType::Dir),
             "l" => Ok(EntryType::Link),
+            "s" => Ok(EntryType::Socket),
+            "p" => Ok(EntryType::Fifo),
+            "b" => Ok(EntryType::Block),
+            "c" => Ok(EntryType::Char),
             _ => Err(EntryTypeError {
                 entry_type: input.to_string(),
             }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        civil_from_days, days_from_civil, format_time, parse_printf, parse_timestamp, Directive,
+    };
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+    }
+
+    #[test]
+    fn test_days_from_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 2, 29), 11_016);
+        assert_eq!(days_from_civil(2024, 1, 1), 19_723);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let jan1 = UNIX_EPOCH + Duration::from_secs(19_723 * 86_400);
+        assert_eq!(parse_timestamp("2024-01-01").unwrap(), jan1);
+        assert_eq!(parse_timestamp("2024-01-01T00:00:00Z").unwrap(), jan1);
+        assert_eq!(
+            parse_timestamp("2024-01-01T12:30:15+02:00").unwrap(),
+            jan1 + Duration::from_secs(10 * 3600 + 30 * 60 + 15)
+        );
+        assert_eq!(
+            parse_timestamp("2023-12-31t23:00:00.5-01:00").unwrap(),
+            jan1 + Duration::from_millis(500)
+        );
+        assert_eq!(
+            parse_timestamp("1969-12-31").unwrap(),
+            UNIX_EPOCH - Duration::from_secs(86_400)
+        );
+
+        for bad in [
+            "",
+            "2024",
+            "2024-1-1",
+            "2024-13-01",
+            "2023-02-29",
+            "2024-01-01T25:00:00Z",
+        ] {
+            let res = parse_timestamp(bad);
+            assert!(res.is_err());
+            assert_eq!(res.unwrap_err().to_string(), bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_printf() {
+        assert_eq!(
+            parse_printf("%p %s\\n").unwrap(),
+            vec![
+                Directive::Path,
+                Directive::Literal(" ".to_string()),
+                Directive::Size,
+                Directive::Literal("\n".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_printf("%f\\t%y 100%%").unwrap(),
+            vec![
+                Directive::Name,
+                Directive::Literal("\t".to_string()),
+                Directive::Type,
+                Directive::Literal(" 100%".to_string()),
+            ]
+        );
+        assert_eq!(parse_printf("").unwrap(), vec![]);
+        assert_eq!(
+            parse_printf("%x").unwrap_err().to_string(),
+            "unknown directive %x"
+        );
+        assert_eq!(parse_printf("%").unwrap_err().to_string(), "trailing %");
+        assert_eq!(
+            parse_printf("\\q").unwrap_err().to_string(),
+            "unknown escape \\q"
+        );
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(UNIX_EPOCH), "1970-01-01 00:00");
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(format_time(time), "2023-11-14 22:13");
+    }
+}
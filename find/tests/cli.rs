@@ -2,6 +2,8 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
 use std::{borrow::Cow, fs, path::Path};
+#[cfg(unix)]
+use tempfile::tempdir;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -39,7 +41,7 @@ fn skips_bad_dir() -> TestResult {
 #[test]
 fn dies_bad_name() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&["--name", "*.csv"])
+        .args(["--name", "*.csv"])
         .assert()
         .failure()
         .stderr(predicate::str::contains("Invalid --name \"*.csv\""));
@@ -51,7 +53,7 @@ fn dies_bad_name() -> TestResult {
 fn dies_bad_type() -> TestResult {
     let expected = "error: 'x' isn't a valid value for '--type <TYPE>...'";
     Command::cargo_bin(PRG)?
-        .args(&["--type", "x"])
+        .args(["--type", "x"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -67,7 +69,7 @@ fn format_file_name(expected_file: &str) -> Cow<str> {
 
 // --------------------------------------------------
 #[cfg(not(windows))]
-fn format_file_name(expected_file: &str) -> Cow<str> {
+fn format_file_name(expected_file: &str) -> Cow<'_, str> {
     // Equivalent to: Cow::Borrowed(expected_file)
     expected_file.into()
 }
@@ -222,6 +224,354 @@ fn type_f_l() -> TestResult {
     )
 }
 
+// --------------------------------------------------
+#[cfg(unix)]
+fn find_in(dir: &Path, args: &[&str]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(dir)
+        .args(args)
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<String> = stdout.lines().map(str::to_string).collect();
+    lines.sort();
+    Ok(lines)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn type_p() -> TestResult {
+    let dir = tempdir()?;
+    let fifo = dir.path().join("pipe");
+    fs::write(dir.path().join("file.txt"), "")?;
+    std::process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .expect("failed");
+
+    let lines = find_in(dir.path(), &["--type", "p"])?;
+    assert_eq!(lines, vec![fifo.display().to_string()]);
+
+    let lines = find_in(dir.path(), &["--type", "f", "p"])?;
+    assert_eq!(
+        lines,
+        vec![
+            dir.path().join("file.txt").display().to_string(),
+            fifo.display().to_string(),
+        ]
+    );
+
+    let lines = find_in(dir.path(), &["--type", "s", "b", "c"])?;
+    assert!(lines.is_empty());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn type_c() -> TestResult {
+    let lines = find_in(Path::new("/dev/null"), &["--type", "c"])?;
+    assert_eq!(lines, vec!["/dev/null"]);
+
+    let lines = find_in(Path::new("/dev/null"), &["--type", "f", "b"])?;
+    assert!(lines.is_empty());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn long() -> TestResult {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let dir = tempdir()?;
+    let path = dir.path().join("file.txt");
+    fs::write(&path, "hello")?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640))?;
+    fs::File::options()
+        .write(true)
+        .open(&path)?
+        .set_modified(UNIX_EPOCH + Duration::from_secs(1_700_000_000))?;
+
+    let lines = find_in(dir.path(), &["--type", "f", "--long"])?;
+    assert_eq!(
+        lines,
+        vec![format!(
+            "-rw-r-----        5 2023-11-14 22:13 {}",
+            path.display()
+        )]
+    );
+
+    let lines = find_in(dir.path(), &["--type", "d", "--ls"])?;
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].starts_with('d'));
+    assert!(lines[0].ends_with(&dir.path().display().to_string()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn newermt() -> TestResult {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let dir = tempdir()?;
+    let old = dir.path().join("old.txt");
+    let new = dir.path().join("new.txt");
+    for (path, secs) in [(&old, 1_700_000_000), (&new, 1_710_000_000)] {
+        fs::write(path, "")?;
+        fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(secs))?;
+    }
+
+    let expected = vec![new.display().to_string()];
+    let lines = find_in(dir.path(), &["--type", "f", "--newermt", "2024-01-01"])?;
+    assert_eq!(lines, expected);
+
+    let lines = find_in(
+        dir.path(),
+        &["--type", "f", "--newermt", "2024-03-09T16:00:00+01:00"],
+    )?;
+    assert_eq!(lines, expected);
+
+    let lines = find_in(dir.path(), &["--type", "f", "--newermt", "2024-12-31"])?;
+    assert!(lines.is_empty());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_newermt() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--newermt", "2024-02-30"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            r#"Invalid --newermt "2024-02-30""#,
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ipath_a_b_c() -> TestResult {
+    run(
+        &["tests/inputs", "--ipath", "A.B.C"],
+        "tests/expected/ipath_a_b_c.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn path_is_case_sensitive() -> TestResult {
+    run(
+        &["tests/inputs", "--path", "a.b.c"],
+        "tests/expected/ipath_a_b_c.txt",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--path", "A.B.C"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_ipath() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--ipath", "("])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --ipath \"(\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn printf_path_size() -> TestResult {
+    run(
+        &["tests/inputs", "-t", "f", "--printf", r"%p %s\n"],
+        "tests/expected/printf_path_size.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn printf_name() -> TestResult {
+    run(
+        &["tests/inputs/a", "--printf", r"%f\n"],
+        "tests/expected/printf_name.txt",
+    )?;
+
+    // Nothing but the format is printed, not even a newline
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/g.csv", "--printf", "%f:%y"])
+        .assert()
+        .success()
+        .stdout("g.csv:f");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_printf() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--printf", "%q"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid --printf \"%q\": unknown directive %q",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn max_results() -> TestResult {
+    let output = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--max-results", "3"])
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?.lines().count(), 3);
+
+    // The limit counts matches, not visited entries
+    let output = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-t", "f", "-n", "csv", "--max-results", "2"])
+        .output()
+        .expect("fail");
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(stdout.lines().all(|line| line.ends_with(".csv")));
+
+    // The walk ends at the last result, so the missing path is never reached
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/g.csv", &bad, "--max-results", "1"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/g.csv\n")
+        .stderr("");
+
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--max-results", "0"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_max_results() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--max-results", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --max-results \"x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn delete_dry_run() -> TestResult {
+    let dir = tempdir()?;
+    let sub = dir.path().join("sub");
+    fs::create_dir(&sub)?;
+    fs::write(sub.join("a.log"), "")?;
+    fs::write(dir.path().join("b.txt"), "")?;
+
+    let lines = find_in(dir.path(), &["--name", "[.]log$", "--delete"])?;
+    assert_eq!(
+        lines,
+        vec![format!("would delete {}", sub.join("a.log").display())]
+    );
+    assert!(sub.join("a.log").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn delete_yes() -> TestResult {
+    let dir = tempdir()?;
+    let sub = dir.path().join("sub");
+    fs::create_dir(&sub)?;
+    fs::write(sub.join("a.log"), "")?;
+    fs::write(dir.path().join("b.txt"), "")?;
+
+    // Depth-first, so "sub" is already empty by the time it is removed
+    let lines = find_in(dir.path(), &["--path", "sub", "--delete", "--yes"])?;
+    assert!(lines.is_empty());
+    assert!(!sub.exists());
+    assert!(dir.path().join("b.txt").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_yes_without_delete() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--yes"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--delete"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_delete_and_long() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--delete", "--long"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_dir() -> TestResult {
+    let dir = tempdir()?;
+    let sub = dir.path().join("sub");
+    fs::create_dir(&sub)?;
+    fs::write(sub.join("a.txt"), "")?;
+    fs::write(dir.path().join("b.txt"), "")?;
+
+    let script = r#"echo "$(pwd -P) $1""#;
+    let lines = find_in(
+        dir.path(),
+        &[
+            "--type",
+            "f",
+            "--exec-dir",
+            "sh",
+            "-c",
+            script,
+            "sh",
+            "{}",
+            ";",
+        ],
+    )?;
+    let root = dir.path().canonicalize()?;
+    assert_eq!(
+        lines,
+        vec![
+            format!("{} ./b.txt", root.display()),
+            format!("{} ./a.txt", root.join("sub").display()),
+        ]
+    );
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn name_csv() -> TestResult {
@@ -293,7 +643,7 @@ fn unreadable_dir() -> TestResult {
     //permissions.set_mode(0o000);
 
     std::process::Command::new("chmod")
-        .args(&["000", dirname])
+        .args(["000", dirname])
         .status()
         .expect("failed");
 
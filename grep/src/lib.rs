@@ -1,21 +1,109 @@
 use clap::{App, Arg};
+use common::LineWriter;
+use encoding_rs::{Encoding, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
 use regex::{Regex, RegexBuilder};
 use std::{
+    cmp::Ordering,
+    collections::VecDeque,
     error::Error,
+    fmt,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, IsTerminal, Read, Write},
 };
 use walkdir::WalkDir;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+/// Why a search pattern could not be compiled.
+#[derive(Debug, PartialEq)]
+pub enum PatternError {
+    /// The pattern is not a valid regular expression.
+    Invalid(String),
+    /// The compiled pattern would exceed this size limit, in bytes.
+    TooBig(usize),
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Invalid(pattern) => write!(f, "Invalid pattern \"{}\"", pattern),
+            PatternError::TooBig(limit) => {
+                write!(f, "Pattern exceeds --size-limit of {} bytes", limit)
+            }
+        }
+    }
+}
+
+impl Error for PatternError {}
+
 #[derive(Debug)]
 pub struct Config {
     pattern: Regex,
     files: Vec<String>,
     recursive: bool,
+    sort_files: bool,
     count: bool,
+    sum: bool,
+    require_all: bool,
+    min_count: Option<usize>,
     invert_match: bool,
+    multiline: bool,
+    byte_offset: bool,
+    line_number: bool,
+    only_matching: bool,
+    context: Context,
+    max_count: Option<usize>,
+    quiet: bool,
+    null_data: bool,
+    passthru: bool,
+    color: bool,
+    encoding: &'static Encoding,
+}
+
+/// How a search ended; `code` gives the matching exit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// At least one line was selected.
+    Matched,
+    /// No line was selected.
+    NoMatch,
+    /// An input could not be read or the arguments were invalid.
+    Error,
+}
+
+impl Status {
+    /// The exit status GNU grep uses: 0, 1 or 2.
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Matched => 0,
+            Status::NoMatch => 1,
+            Status::Error => 2,
+        }
+    }
+}
+
+/// A selected input line, its 1-based line number and the byte offset at
+/// which it starts. Context lines are printed around the selected ones.
+#[derive(Debug, PartialEq)]
+struct Line {
+    number: usize,
+    offset: usize,
+    text: String,
+    context: bool,
+}
+
+/// How many lines to print before and after each selected line.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Context {
+    before: usize,
+    after: usize,
+}
+
+impl Context {
+    fn is_empty(&self) -> bool {
+        self.before == 0 && self.after == 0
+    }
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -26,9 +114,26 @@ pub fn get_args() -> MyResult<Config> {
         .arg(
             Arg::with_name("pattern")
                 .value_name("PATTERN")
-                .required(true)
+                .required_unless_one(&["regexp", "pattern_file"])
                 .help("Search pattern"),
         )
+        .arg(
+            Arg::with_name("regexp")
+                .short("e")
+                .long("regexp")
+                .value_name("PATTERN")
+                .multiple(true)
+                .number_of_values(1)
+                .allow_hyphen_values(true)
+                .help("Search for PATTERN; may be repeated"),
+        )
+        .arg(
+            Arg::with_name("pattern_file")
+                .short("f")
+                .long("file")
+                .value_name("FILE")
+                .help("Read patterns from FILE, one per line"),
+        )
         .arg(
             Arg::with_name("files")
                 .value_name("FILE")
@@ -36,18 +141,72 @@ pub fn get_args() -> MyResult<Config> {
                 .default_value("-")
                 .help("Input file(s)"),
         )
+        .arg(
+            Arg::with_name("files_from")
+                .long("files-from")
+                .value_name("LIST")
+                .conflicts_with("files")
+                .help("Read files to search from LIST, one per line (\"-\" for stdin)"),
+        )
+        .arg(
+            Arg::with_name("fixed_strings")
+                .short("F")
+                .long("fixed-strings")
+                .help("Match patterns as literal strings, not regular expressions"),
+        )
+        .arg(
+            Arg::with_name("word_regexp")
+                .short("w")
+                .long("word-regexp")
+                .help("Only match whole words"),
+        )
+        .arg(
+            Arg::with_name("ascii_boundaries")
+                .long("ascii-boundaries")
+                .requires("word_regexp")
+                .help("Only ASCII letters, digits and _ are word characters for -w"),
+        )
+        .arg(
+            Arg::with_name("line_regexp")
+                .short("x")
+                .long("line-regexp")
+                .help("Only match whole lines"),
+        )
         .arg(
             Arg::with_name("recursive")
                 .short("r")
                 .long("recursive")
                 .help("Recursive search"),
         )
+        .arg(
+            Arg::with_name("sort_files")
+                .long("sort-files")
+                .help("Search files in lexicographic order"),
+        )
         .arg(
             Arg::with_name("count")
                 .short("c")
                 .long("count")
                 .help("Count occurrences"),
         )
+        .arg(
+            Arg::with_name("sum")
+                .long("sum")
+                .help("Print only the total count of matching lines across all files"),
+        )
+        .arg(
+            Arg::with_name("require_all")
+                .long("require-all")
+                .conflicts_with_all(&["count", "sum", "passthru"])
+                .help("List files without a match instead, failing if there are any"),
+        )
+        .arg(
+            Arg::with_name("min_count")
+                .long("min-count")
+                .value_name("NUM")
+                .requires("count")
+                .help("Only count files with at least NUM matches"),
+        )
         .arg(
             Arg::with_name("invert_match")
                 .short("v")
@@ -60,61 +219,499 @@ pub fn get_args() -> MyResult<Config> {
                 .long("insensitive")
                 .help("Case-insensitive"),
         )
+        .arg(
+            Arg::with_name("multiline")
+                .long("multiline")
+                .help("Match across line boundaries"),
+        )
+        .arg(
+            Arg::with_name("byte_offset")
+                .short("b")
+                .long("byte-offset")
+                .help("Print the byte offset of each line"),
+        )
+        .arg(
+            Arg::with_name("line_number")
+                .short("n")
+                .long("line-number")
+                .help("Print the line number of each line"),
+        )
+        .arg(
+            Arg::with_name("only_matching")
+                .short("o")
+                .long("only-matching")
+                .conflicts_with_all(&["invert_match", "multiline", "passthru"])
+                .help("Print only the matched parts of each line, one per line"),
+        )
+        .arg(
+            Arg::with_name("after_context")
+                .short("A")
+                .long("after-context")
+                .value_name("NUM")
+                .conflicts_with_all(&["multiline", "only_matching", "passthru"])
+                .help("Print NUM lines after each selected line"),
+        )
+        .arg(
+            Arg::with_name("before_context")
+                .short("B")
+                .long("before-context")
+                .value_name("NUM")
+                .conflicts_with_all(&["multiline", "only_matching", "passthru"])
+                .help("Print NUM lines before each selected line"),
+        )
+        .arg(
+            Arg::with_name("context")
+                .short("C")
+                .long("context")
+                .value_name("NUM")
+                .conflicts_with_all(&["multiline", "only_matching", "passthru"])
+                .help("Print NUM lines around each selected line"),
+        )
+        .arg(
+            Arg::with_name("max_count")
+                .short("m")
+                .long("max-count")
+                .value_name("NUM")
+                .help("Stop reading a file after NUM selected lines"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .alias("silent")
+                .help("Print nothing; exit at the first match"),
+        )
+        .arg(
+            Arg::with_name("null_data")
+                .short("z")
+                .long("null-data")
+                .conflicts_with("multiline")
+                .help("Input and output records are NUL-terminated, not lines"),
+        )
+        .arg(
+            Arg::with_name("passthru")
+                .long("passthru")
+                .conflicts_with_all(&["count", "sum", "invert_match", "multiline"])
+                .help("Print all lines, not just selected ones"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .alias("colour")
+                .value_name("WHEN")
+                .possible_values(&["auto", "always", "never"])
+                .min_values(0)
+                .require_equals(true)
+                .help("Highlight matches: auto, always or never [default: never]"),
+        )
+        .arg(
+            Arg::with_name("size_limit")
+                .long("size-limit")
+                .value_name("BYTES")
+                .help("Fail if the compiled pattern would need more than BYTES"),
+        )
+        .arg(
+            Arg::with_name("encoding")
+                .long("encoding")
+                .value_name("NAME")
+                .default_value("utf-8")
+                .help("Decode input from this encoding, e.g. latin1 or shift_jis"),
+        )
         .get_matches();
     let insensitive = matches.is_present("insensitive");
-    let pattern_args = &matches.value_of_lossy("pattern").unwrap();
+    let multiline = matches.is_present("multiline");
+    // With -e or -f, the first positional argument is a file, not the pattern
+    let mut patterns = matches.values_of_lossy("regexp").unwrap_or_default();
+    if let Some(file) = matches.value_of("pattern_file") {
+        patterns.extend(read_patterns(file).map_err(|e| format!("{}: {}", file, e))?);
+    }
+    let mut files = matches.values_of_lossy("files").unwrap();
+    match matches.value_of_lossy("pattern") {
+        Some(file) if matches.is_present("regexp") || matches.is_present("pattern_file") => {
+            if matches.is_present("files_from") {
+                return Err(From::from(
+                    "FILE arguments cannot be used with --files-from",
+                ));
+            }
+            if matches.occurrences_of("files") == 0 {
+                files.clear();
+            }
+            files.insert(0, file.into_owned());
+        }
+        Some(pattern) => patterns.push(pattern.into_owned()),
+        None => {}
+    }
+    if matches.is_present("fixed_strings") {
+        patterns = patterns
+            .iter()
+            .map(|pattern| regex::escape(pattern))
+            .collect();
+    }
+    // -x takes precedence over -w, as in GNU grep
+    let anchors = if matches.is_present("line_regexp") {
+        Some(("^", "$"))
+    } else if matches.is_present("ascii_boundaries") {
+        Some((r"(?-u:\b)", r"(?-u:\b)"))
+    } else if matches.is_present("word_regexp") {
+        Some((r"\b", r"\b"))
+    } else {
+        None
+    };
+    if let Some((start, end)) = anchors {
+        patterns = patterns
+            .iter()
+            .map(|pattern| format!("{}(?:{}){}", start, pattern, end))
+            .collect();
+    }
+    let encoding = matches.value_of("encoding").unwrap();
+    let size_limit = matches
+        .value_of("size_limit")
+        .map(|n| {
+            n.parse()
+                .map_err(|_| format!("Invalid --size-limit \"{}\"", n))
+        })
+        .transpose()?;
+    // -A and -B take precedence over -C, as in GNU grep
+    let parse_context = |name: &str, flag: &str| {
+        matches
+            .value_of(name)
+            .map(|n| n.parse().map_err(|_| format!("Invalid {} \"{}\"", flag, n)))
+            .transpose()
+    };
+    let both = parse_context("context", "-C")?.unwrap_or(0);
+    let context = Context {
+        before: parse_context("before_context", "-B")?.unwrap_or(both),
+        after: parse_context("after_context", "-A")?.unwrap_or(both),
+    };
     Ok(Config {
-        pattern: RegexBuilder::new(pattern_args)
-            .case_insensitive(insensitive)
-            .build()
-            .map_err(|_| format!("Invalid pattern \"{}\"", pattern_args))?,
-        files: matches.values_of_lossy("files").unwrap(),
+        pattern: build_patterns(&patterns, insensitive, multiline, size_limit)?,
+        files: match matches.value_of("files_from") {
+            Some(list) => read_file_list(list).map_err(|e| format!("{}: {}", list, e))?,
+            None => files,
+        },
         recursive: matches.is_present("recursive"),
+        sort_files: matches.is_present("sort_files"),
         count: matches.is_present("count"),
+        sum: matches.is_present("sum"),
+        require_all: matches.is_present("require_all"),
+        min_count: matches
+            .value_of("min_count")
+            .map(|n| {
+                n.parse()
+                    .map_err(|_| format!("Invalid --min-count \"{}\"", n))
+            })
+            .transpose()?,
         invert_match: matches.is_present("invert_match"),
+        multiline,
+        byte_offset: matches.is_present("byte_offset"),
+        line_number: matches.is_present("line_number"),
+        only_matching: matches.is_present("only_matching"),
+        context,
+        max_count: matches
+            .value_of("max_count")
+            .map(|n| {
+                n.parse()
+                    .map_err(|_| format!("Invalid --max-count \"{}\"", n))
+            })
+            .transpose()?,
+        quiet: matches.is_present("quiet"),
+        null_data: matches.is_present("null_data"),
+        passthru: matches.is_present("passthru"),
+        // A bare --color means auto, as in GNU grep
+        color: match matches.value_of("color") {
+            Some("always") => true,
+            Some("never") => false,
+            _ if matches.is_present("color") => io::stdout().is_terminal(),
+            _ => false,
+        },
+        encoding: Encoding::for_label(encoding.as_bytes())
+            .ok_or_else(|| format!("Invalid --encoding \"{}\"", encoding))?,
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    let entries = find_files(&config.files, config.recursive);
+/// Compiles a search pattern the way the command line does.
+pub fn build_pattern(
+    pattern: &str,
+    insensitive: bool,
+    multiline: bool,
+    size_limit: Option<usize>,
+) -> Result<Regex, PatternError> {
+    let mut builder = RegexBuilder::new(pattern);
+    builder
+        .case_insensitive(insensitive)
+        .multi_line(multiline)
+        .dot_matches_new_line(multiline);
+    if let Some(limit) = size_limit {
+        builder.size_limit(limit).dfa_size_limit(limit);
+    }
+    builder.build().map_err(|e| match e {
+        regex::Error::CompiledTooBig(limit) => PatternError::TooBig(limit),
+        _ => PatternError::Invalid(pattern.to_string()),
+    })
+}
+
+/// Compiles several patterns into one that matches wherever any of them
+/// does; an invalid pattern is reported on its own.
+pub fn build_patterns(
+    patterns: &[String],
+    insensitive: bool,
+    multiline: bool,
+    size_limit: Option<usize>,
+) -> Result<Regex, PatternError> {
+    let pattern = match patterns {
+        [pattern] => pattern.to_owned(),
+        // Matches nothing, like GNU grep given an empty -f file
+        [] => r"[^\s\S]".to_owned(),
+        _ => {
+            for pattern in patterns {
+                build_pattern(pattern, insensitive, multiline, None)?;
+            }
+            patterns
+                .iter()
+                .map(|pattern| format!("(?:{})", pattern))
+                .collect::<Vec<_>>()
+                .join("|")
+        }
+    };
+    build_pattern(&pattern, insensitive, multiline, size_limit)
+}
+
+pub fn run(config: Config) -> MyResult<Status> {
+    let entries = find_files(&config.files, config.recursive, config.sort_files);
+    // --passthru selects every line; the pattern is only used to highlight
+    let match_all = Regex::new("").unwrap();
+    let (select, invert_match) = if config.passthru && !config.quiet {
+        (&match_all, false)
+    } else {
+        (&config.pattern, config.invert_match)
+    };
+    let terminator = if config.null_data { b'\0' } else { b'\n' };
+    let mut out = LineWriter::new(io::stdout(), terminator);
+    // Counting only looks at selected lines
+    let context = if config.count || config.sum || config.require_all || config.quiet {
+        Context::default()
+    } else {
+        config.context
+    };
+    // One selected line is enough to decide the exit status
+    let max_count = if config.quiet {
+        Some(1)
+    } else {
+        config.max_count
+    };
+    let mut total = 0;
+    let mut unmatched = 0;
+    let mut printed_group = false;
+    let mut matched = false;
+    let mut errored = false;
     for entry in &entries {
         match entry {
-            Err(e) => eprintln!("{}", e),
-            Ok(filename) => match open(&filename) {
-                Err(e) => eprintln!("{}: {}", filename, e),
+            Err(e) => {
+                eprintln!("{}", e);
+                errored = true;
+            }
+            Ok(filename) => match open(filename, config.encoding) {
+                Err(e) => {
+                    eprintln!("{}: {}", filename, e);
+                    errored = true;
+                }
                 Ok(file) => {
-                    let matches = find_lines(file, &config.pattern, config.invert_match);
+                    let matches = if config.multiline {
+                        find_lines_multiline(file, &config.pattern, config.invert_match).map(
+                            |mut lines| {
+                                lines.truncate(max_count.unwrap_or(usize::MAX));
+                                lines
+                            },
+                        )
+                    } else {
+                        find_lines(file, select, invert_match, terminator, context, max_count)
+                    };
                     match matches {
-                        Err(e) => eprintln!("{}", e),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            errored = true;
+                        }
                         Ok(lines) => {
-                            print_matches(lines, &filename, entries.len() > 1, config.count)
+                            // Passed-through lines only count if the pattern matches them
+                            let selected = lines.iter().any(|line| {
+                                !line.context
+                                    && (!config.passthru || config.pattern.is_match(&line.text))
+                            });
+                            matched |= selected;
+                            // Files under --min-count aren't reported, but
+                            // their lines still matched
+                            if config.min_count.is_some_and(|n| lines.len() < n) {
+                                continue;
+                            }
+                            if config.require_all {
+                                // Every file must be checked, even with -q
+                                if lines.is_empty() {
+                                    unmatched += 1;
+                                    if !config.quiet {
+                                        out.write_record(filename)?;
+                                    }
+                                }
+                            } else if config.quiet {
+                                if selected {
+                                    return Ok(Status::Matched);
+                                }
+                            } else if config.sum {
+                                total += lines.len();
+                            } else {
+                                let multiple = entries.len() > 1;
+                                printed_group |= print_matches(
+                                    &mut out,
+                                    lines,
+                                    filename,
+                                    multiple,
+                                    printed_group,
+                                    &config,
+                                )?;
+                            }
                         }
                     }
                 }
             },
         }
     }
-    Ok(())
+    if config.sum && !config.quiet {
+        println!("{}", total);
+    }
+    if unmatched > 0 {
+        return Err(From::from(format!(
+            "{} of {} files have no match for \"{}\"",
+            unmatched,
+            entries.len(),
+            config.pattern
+        )));
+    }
+    // As in GNU grep, a read error outweighs any match
+    Ok(if errored {
+        Status::Error
+    } else if matched {
+        Status::Matched
+    } else {
+        Status::NoMatch
+    })
 }
 
-fn print_matches(mut matches: Vec<String>, filename: &str, multiple_entries: bool, count: bool) {
-    if count {
-        matches = vec![matches.len().to_string()];
+// With context lines, groups that aren't adjacent, including those from
+// an earlier file, are separated by "--". Returns whether anything was printed.
+fn print_matches(
+    out: &mut LineWriter<impl Write>,
+    matches: Vec<Line>,
+    filename: &str,
+    multiple_entries: bool,
+    printed_group: bool,
+    config: &Config,
+) -> MyResult<bool> {
+    let filename = if multiple_entries {
+        Some(filename)
+    } else {
+        None
+    };
+    if config.count {
+        match filename {
+            Some(filename) => println!("{}:{}", filename, matches.len()),
+            None => println!("{}", matches.len()),
+        }
+        return Ok(true);
     }
-    for m in matches {
-        println!(
-            "{}{}",
-            if multiple_entries {
-                format!("{}:", filename)
-            } else {
-                "".to_owned()
-            },
-            m
-        );
+    let printed = !matches.is_empty();
+    let mut last_number = None;
+    for mut m in matches {
+        if !config.context.is_empty() {
+            let adjacent = last_number.map(|n| n + 1 == m.number);
+            if adjacent == Some(false) || (adjacent.is_none() && printed_group) {
+                out.write_record("--")?;
+            }
+            last_number = Some(m.number);
+        }
+        if config.only_matching {
+            // Each match is its own record; -b gives where the match starts
+            for found in config.pattern.find_iter(&m.text) {
+                if !found.as_str().is_empty() {
+                    let offset = m.offset + found.start();
+                    let text = if config.color {
+                        colorize(found.as_str())
+                    } else {
+                        found.as_str().to_string()
+                    };
+                    out.write_record(format_record(filename, &m, offset, &text, config))?;
+                }
+            }
+            continue;
+        }
+        // Lines --passthru prints without a match are labelled as context;
+        // this must be decided before highlighting changes the text
+        if config.passthru && !config.pattern.is_match(&m.text) {
+            m.context = true;
+        }
+        if config.color && !m.context {
+            m.text = highlight(&m.text, &config.pattern);
+        }
+        out.write_record(format_record(filename, &m, m.offset, &m.text, config))?;
     }
+    Ok(printed)
+}
+
+// Same order as GNU grep: file, line number, byte offset, then the text.
+// Context lines use "-" instead of ":" after each field.
+fn format_record(
+    filename: Option<&str>,
+    line: &Line,
+    offset: usize,
+    text: &str,
+    config: &Config,
+) -> String {
+    let sep = if line.context { '-' } else { ':' };
+    let mut record = String::new();
+    if let Some(filename) = filename {
+        record.push_str(&format!("{}{}", filename, sep));
+    }
+    if config.line_number {
+        record.push_str(&format!("{}{}", line.number, sep));
+    }
+    if config.byte_offset {
+        record.push_str(&format!("{}{}", offset, sep));
+    }
+    record.push_str(text);
+    record
+}
+
+// Every line is a pattern; a blank one matches every line, as in GNU grep
+fn read_patterns(file: &str) -> MyResult<Vec<String>> {
+    Ok(open(file, UTF_8)?.lines().collect::<io::Result<_>>()?)
 }
 
-fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
+// Blank lines are skipped; other lines are taken verbatim as paths
+fn read_file_list(list: &str) -> MyResult<Vec<String>> {
+    open(list, UTF_8)?
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+        .map(|line| Ok(line?))
+        .collect()
+}
+
+/// Wraps text in the SGR sequence GNU grep uses for matches.
+fn colorize(text: &str) -> String {
+    format!("\x1b[01;31m{}\x1b[0m", text)
+}
+
+/// Colorizes each non-empty match of `pattern` in `text`.
+fn highlight(text: &str, pattern: &Regex) -> String {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut last = 0;
+    for m in pattern.find_iter(text).filter(|m| !m.as_str().is_empty()) {
+        highlighted.push_str(&text[last..m.start()]);
+        highlighted.push_str(&colorize(m.as_str()));
+        last = m.end();
+    }
+    highlighted.push_str(&text[last..]);
+    highlighted
+}
+
+fn find_files(paths: &[String], recursive: bool, sort: bool) -> Vec<MyResult<String>> {
     let mut files: Vec<MyResult<String>> = Vec::new();
 
     for path in paths {
@@ -156,49 +753,230 @@ fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
             }
         }
     }
+    if sort {
+        // Errors keep their relative order ahead of the sorted files
+        files.sort_by(|a, b| match (a, b) {
+            (Ok(a), Ok(b)) => a.cmp(b),
+            (Err(_), Ok(_)) => Ordering::Less,
+            (Ok(_), Err(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => Ordering::Equal,
+        });
+    }
     files
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+// Input in any encoding other than UTF-8 is transcoded to UTF-8 as it is read,
+// so byte offsets refer to the decoded text
+fn open(filename: &str, encoding: &'static Encoding) -> MyResult<Box<dyn BufRead>> {
+    let file: Box<dyn Read> = match filename {
+        "-" => Box::new(io::stdin()),
+        _ => Box::new(File::open(filename)?),
+    };
+    if encoding == UTF_8 {
+        return Ok(Box::new(BufReader::new(file)));
     }
+    Ok(Box::new(BufReader::new(
+        DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding))
+            .build(file),
+    )))
 }
 
-fn find_lines<T: BufRead>(file: T, pattern: &Regex, invert_match: bool) -> MyResult<Vec<String>> {
+/// Selects the records ending in `terminator`: newline-terminated lines, or
+/// NUL-terminated records (which may contain newlines) under -z. Lines
+/// within `context` of a selected one are included, marked as context.
+/// Reading stops after `max_count` selected lines and their trailing context.
+fn find_lines<T: BufRead>(
+    mut file: T,
+    pattern: &Regex,
+    invert_match: bool,
+    terminator: u8,
+    context: Context,
+    max_count: Option<usize>,
+) -> MyResult<Vec<Line>> {
     let mut results = Vec::new();
-    for line in file.lines() {
-        let line = line?;
-        let reg = pattern.captures(&line);
-        if reg.is_some() ^ invert_match {
-            results.push(line);
+    // Unselected lines that may yet turn out to precede a selected one
+    let mut before: VecDeque<Line> = VecDeque::with_capacity(context.before + 1);
+    let mut after = 0;
+    let mut remaining = max_count.unwrap_or(usize::MAX);
+    let mut offset = 0;
+    let mut number = 0;
+    let mut buf = Vec::new();
+    loop {
+        if remaining == 0 && after == 0 {
+            break;
+        }
+        buf.clear();
+        let bytes = file.read_until(terminator, &mut buf)?;
+        if bytes == 0 {
+            break;
+        }
+        number += 1;
+        let record = std::str::from_utf8(&buf).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )
+        })?;
+        let line = if terminator == b'\n' {
+            strip_newline(record)
+        } else {
+            record.strip_suffix(terminator as char).unwrap_or(record)
+        };
+        if remaining > 0 && pattern.is_match(line) ^ invert_match {
+            remaining -= 1;
+            results.extend(before.drain(..));
+            results.push(Line {
+                number,
+                offset,
+                text: line.to_string(),
+                context: false,
+            });
+            after = context.after;
+        } else if after > 0 {
+            results.push(Line {
+                number,
+                offset,
+                text: line.to_string(),
+                context: true,
+            });
+            after -= 1;
+        } else if context.before > 0 {
+            if before.len() == context.before {
+                before.pop_front();
+            }
+            before.push_back(Line {
+                number,
+                offset,
+                text: line.to_string(),
+                context: true,
+            });
         }
+        offset += bytes;
     }
 
     Ok(results)
 }
 
+// Mirrors BufRead::lines, which drops "\n" and a single preceding "\r"
+fn strip_newline(line: &str) -> &str {
+    match line.strip_suffix('\n') {
+        Some(line) => line.strip_suffix('\r').unwrap_or(line),
+        None => line,
+    }
+}
+
+/// Reads the whole input so the pattern can span lines; every line touched by a match is selected.
+fn find_lines_multiline<T: BufRead>(
+    mut file: T,
+    pattern: &Regex,
+    invert_match: bool,
+) -> MyResult<Vec<Line>> {
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        lines.push((start, start + line.len()));
+        start += line.len();
+    }
+
+    let mut matched = vec![false; lines.len()];
+    for m in pattern.find_iter(&text) {
+        // An empty match still selects the line it occurs on
+        let end = m.end().max(m.start() + 1);
+        // Only the lines the match spans need marking, starting with the
+        // first one that ends after the match starts
+        let first = lines.partition_point(|&(_, line_end)| line_end <= m.start());
+        for (i, (line_start, _)) in lines.iter().enumerate().skip(first) {
+            if *line_start >= end {
+                break;
+            }
+            matched[i] = true;
+        }
+    }
+
+    Ok(lines
+        .into_iter()
+        .zip(matched)
+        .enumerate()
+        .filter(|(_, (_, is_match))| is_match ^ invert_match)
+        .map(|(i, ((line_start, line_end), _))| Line {
+            number: i + 1,
+            offset: line_start,
+            text: strip_newline(&text[line_start..line_end]).to_string(),
+            context: false,
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{find_files, find_lines};
+    use super::{
+        build_pattern, build_patterns, find_files, find_lines, find_lines_multiline, Context, Line,
+        PatternError,
+    };
     use rand::{distributions::Alphanumeric, Rng};
     use regex::{Regex, RegexBuilder};
     use std::io::Cursor;
 
+    #[test]
+    fn test_build_pattern() {
+        assert_eq!(
+            build_pattern("(", false, false, None).unwrap_err(),
+            PatternError::Invalid("(".to_string())
+        );
+        assert_eq!(
+            build_pattern(r"\w{100}", false, false, Some(1000)).unwrap_err(),
+            PatternError::TooBig(1000)
+        );
+        assert_eq!(
+            PatternError::Invalid("(".to_string()).to_string(),
+            "Invalid pattern \"(\""
+        );
+
+        let re = build_pattern("THE", true, false, None).unwrap();
+        assert!(re.is_match("the"));
+    }
+
+    #[test]
+    fn test_build_patterns() {
+        let patterns = vec!["^a".to_string(), "b$".to_string()];
+        let re = build_patterns(&patterns, false, false, None).unwrap();
+        assert!(re.is_match("ax"));
+        assert!(re.is_match("xb"));
+        assert!(!re.is_match("xax"));
+        // Case-insensitivity applies to every pattern
+        let re = build_patterns(&patterns, true, false, None).unwrap();
+        assert!(re.is_match("AX") && re.is_match("XB"));
+        // A single pattern is used as is
+        let re = build_patterns(&patterns[..1], false, false, None).unwrap();
+        assert_eq!(re.as_str(), "^a");
+        // No patterns match nothing, not even an empty line
+        let re = build_patterns(&[], false, false, None).unwrap();
+        assert!(!re.is_match(""));
+
+        let patterns = vec!["a".to_string(), "(".to_string()];
+        assert_eq!(
+            build_patterns(&patterns, false, false, None).unwrap_err(),
+            PatternError::Invalid("(".to_string())
+        );
+    }
+
     #[test]
     fn test_find_files() {
-        let files = find_files(&["./tests/inputs/fox.txt".to_string()], false);
+        let files = find_files(&["./tests/inputs/fox.txt".to_string()], false, false);
         assert_eq!(files.len(), 1);
         assert_eq!(files[0].as_ref().unwrap(), "./tests/inputs/fox.txt");
 
-        let files = find_files(&["./tests/inputs".to_string()], false);
+        let files = find_files(&["./tests/inputs".to_string()], false, false);
         assert_eq!(files.len(), 1);
         if let Err(e) = &files[0] {
             assert_eq!(e.to_string(), "./tests/inputs is a directory");
         }
 
-        let res = find_files(&["./tests/inputs".to_string()], true);
+        let res = find_files(&["./tests/inputs".to_string()], true, false);
         let mut files: Vec<String> = res
             .iter()
             .map(|r| r.as_ref().unwrap().replace("\\", "/"))
@@ -220,9 +998,20 @@ mod tests {
             .take(7)
             .map(char::from)
             .collect();
-        let files = find_files(&[bad], false);
+        let files = find_files(std::slice::from_ref(&bad), false, false);
         assert_eq!(files.len(), 1);
         assert!(files[0].is_err());
+
+        // Sorting puts errors first, then files in lexicographic order
+        let paths = [
+            "./tests/inputs/fox.txt".to_string(),
+            bad,
+            "./tests/inputs/bustle.txt".to_string(),
+        ];
+        let files = find_files(&paths, false, true);
+        assert!(files[0].is_err());
+        assert_eq!(files[1].as_ref().unwrap(), "./tests/inputs/bustle.txt");
+        assert_eq!(files[2].as_ref().unwrap(), "./tests/inputs/fox.txt");
     }
 
     #[test]
@@ -230,11 +1019,25 @@ mod tests {
         let text = b"Lorem\nIpsum\r\nDOLOR";
         // The pattern _or_ should match the one line, "Lorem"
         let re1 = Regex::new("or").unwrap();
-        let matches = find_lines(Cursor::new(&text), &re1, false);
+        let matches = find_lines(
+            Cursor::new(&text),
+            &re1,
+            false,
+            b'\n',
+            Context::default(),
+            None,
+        );
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 1);
         // When inverted, the function should match the other two lines
-        let matches = find_lines(Cursor::new(&text), &re1, true);
+        let matches = find_lines(
+            Cursor::new(&text),
+            &re1,
+            true,
+            b'\n',
+            Context::default(),
+            None,
+        );
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 2);
         // This regex will be case-insensitive
@@ -243,12 +1046,186 @@ mod tests {
             .build()
             .unwrap();
         // The two lines "Lorem" and "DOLOR" should match
-        let matches = find_lines(Cursor::new(&text), &re2, false);
+        let matches = find_lines(
+            Cursor::new(&text),
+            &re2,
+            false,
+            b'\n',
+            Context::default(),
+            None,
+        );
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 2);
         // When inverted, the one remaining line should match
-        let matches = find_lines(Cursor::new(&text), &re2, true);
+        let matches = find_lines(
+            Cursor::new(&text),
+            &re2,
+            true,
+            b'\n',
+            Context::default(),
+            None,
+        );
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 1);
     }
+
+    #[test]
+    fn test_find_lines_context() {
+        let text = b"a\nb\nmatch\nc\nd\ne\nmatch\nf";
+        let re = Regex::new("match").unwrap();
+        let context = Context {
+            before: 1,
+            after: 2,
+        };
+        let matches = find_lines(Cursor::new(&text), &re, false, b'\n', context, None).unwrap();
+        let numbers: Vec<_> = matches.iter().map(|l| (l.number, l.context)).collect();
+        assert_eq!(
+            numbers,
+            vec![
+                (2, true),
+                (3, false),
+                (4, true),
+                (5, true),
+                (6, true),
+                (7, false),
+                (8, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_lines_max_count() {
+        let text = b"a1\nb\na2\na3\nc\na4";
+        let re = Regex::new("a").unwrap();
+        let matches = find_lines(
+            Cursor::new(&text),
+            &re,
+            false,
+            b'\n',
+            Context::default(),
+            Some(2),
+        );
+        assert_eq!(texts(matches.unwrap()), vec!["a1", "a2"]);
+        // Trailing context still follows the last selected line
+        let context = Context {
+            before: 0,
+            after: 2,
+        };
+        let matches = find_lines(Cursor::new(&text), &re, false, b'\n', context, Some(2));
+        assert_eq!(texts(matches.unwrap()), vec!["a1", "b", "a2", "a3", "c"]);
+        let matches = find_lines(
+            Cursor::new(&text),
+            &re,
+            true,
+            b'\n',
+            Context::default(),
+            Some(0),
+        );
+        assert!(matches.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_lines_null_data() {
+        let text = b"one\ntwo\0three\0four\nfive";
+        let re = Regex::new("e\nt|ve$").unwrap();
+        let matches = find_lines(
+            Cursor::new(&text),
+            &re,
+            false,
+            b'\0',
+            Context::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            matches,
+            vec![
+                Line {
+                    number: 1,
+                    offset: 0,
+                    text: "one\ntwo".to_string(),
+                    context: false
+                },
+                Line {
+                    number: 3,
+                    offset: 14,
+                    text: "four\nfive".to_string(),
+                    context: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_lines_offsets() {
+        let text = b"Lorem\nIpsum\r\nDOLOR";
+        let re = RegexBuilder::new("or")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        // Offsets count the "\r\n" terminator of the skipped line
+        let matches = find_lines(
+            Cursor::new(&text),
+            &re,
+            false,
+            b'\n',
+            Context::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            matches,
+            vec![
+                Line {
+                    number: 1,
+                    offset: 0,
+                    text: "Lorem".to_string(),
+                    context: false
+                },
+                Line {
+                    number: 3,
+                    offset: 13,
+                    text: "DOLOR".to_string(),
+                    context: false
+                },
+            ]
+        );
+        let matches = find_lines_multiline(Cursor::new(&text), &re, true).unwrap();
+        assert_eq!(
+            matches,
+            vec![Line {
+                number: 2,
+                offset: 6,
+                text: "Ipsum".to_string(),
+                context: false
+            }]
+        );
+    }
+
+    fn texts(lines: Vec<Line>) -> Vec<String> {
+        lines.into_iter().map(|l| l.text).collect()
+    }
+
+    #[test]
+    fn test_find_lines_multiline() {
+        let text = b"Lorem\nIpsum\r\nDOLOR\nsit";
+        let re = RegexBuilder::new(r"em\n.*sum")
+            .dot_matches_new_line(true)
+            .build()
+            .unwrap();
+        // The match spans the first two lines
+        let matches = find_lines_multiline(Cursor::new(&text), &re, false);
+        assert_eq!(texts(matches.unwrap()), vec!["Lorem", "Ipsum"]);
+        // When inverted, the lines outside the match are returned
+        let matches = find_lines_multiline(Cursor::new(&text), &re, true);
+        assert_eq!(texts(matches.unwrap()), vec!["DOLOR", "sit"]);
+        // A pattern confined to one line behaves like find_lines
+        let re = Regex::new("or").unwrap();
+        let matches = find_lines_multiline(Cursor::new(&text), &re, false);
+        assert_eq!(texts(matches.unwrap()), vec!["Lorem"]);
+        // Without dotall, "." does not cross the line boundary
+        let re = Regex::new(r"em.Ipsum").unwrap();
+        let matches = find_lines_multiline(Cursor::new(&text), &re, false);
+        assert!(matches.unwrap().is_empty());
+    }
 }
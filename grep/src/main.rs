@@ -1,6 +1,10 @@
 fn main() {
-    if let Err(e) = grep::get_args().and_then(grep::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
-    }
+    let status = match grep::get_args().and_then(grep::run) {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("{}", e);
+            grep::Status::Error
+        }
+    };
+    std::process::exit(status.code());
 }
@@ -11,6 +11,7 @@ const BUSTLE: &str = "tests/inputs/bustle.txt";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const NOBODY: &str = "tests/inputs/nobody.txt";
+const LATIN1: &str = "tests/inputs_encoding/latin1.txt";
 const INPUTS_DIR: &str = "tests/inputs";
 
 // --------------------------------------------------
@@ -42,7 +43,7 @@ fn dies_no_args() -> TestResult {
 #[test]
 fn dies_bad_pattern() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&["*foo", FOX])
+        .args(["*foo", FOX])
         .assert()
         .failure()
         .stderr(predicate::str::contains("Invalid pattern \"*foo\""));
@@ -55,27 +56,39 @@ fn warns_bad_file() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&["foo", &bad])
+        .args(["foo", &bad])
         .assert()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
 
 // --------------------------------------------------
-fn run(args: &[&str], expected_file: &str) -> TestResult {
+fn read_expected(expected_file: &str) -> std::io::Result<String> {
     let windows_file = format!("{}.windows", expected_file);
     let expected_file = if os_type().unwrap() == "Windows" && Path::new(&windows_file).is_file() {
         &windows_file
     } else {
         expected_file
     };
+    fs::read_to_string(expected_file)
+}
 
-    let expected = fs::read_to_string(&expected_file)?;
+// --------------------------------------------------
+fn run(args: &[&str], expected_file: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .stdout(read_expected(expected_file)?);
+    Ok(())
+}
 
+// --------------------------------------------------
+fn run_success(args: &[&str], expected_file: &str) -> TestResult {
     Command::cargo_bin(PRG)?
         .args(args)
         .assert()
-        .stdout(expected);
+        .success()
+        .stdout(read_expected(expected_file)?);
     Ok(())
 }
 
@@ -151,7 +164,7 @@ fn multiple_files_insensitive() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn recursive() -> TestResult {
-    run(
+    run_success(
         &["--recursive", "dog", INPUTS_DIR],
         "tests/expected/dog.recursive",
     )
@@ -160,7 +173,7 @@ fn recursive() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn recursive_insensitive() -> TestResult {
-    run(
+    run_success(
         &["-ri", "then", INPUTS_DIR],
         "tests/expected/the.recursive.insensitive",
     )
@@ -232,7 +245,7 @@ fn warns_dir_not_recursive() -> TestResult {
     let stdout = "tests/inputs/fox.txt:\
         The quick brown fox jumps over the lazy dog.";
     Command::cargo_bin(PRG)?
-        .args(&["fox", INPUTS_DIR, FOX])
+        .args(["fox", INPUTS_DIR, FOX])
         .assert()
         .stderr(predicate::str::contains("tests/inputs is a directory"))
         .stdout(predicate::str::contains(stdout));
@@ -267,9 +280,840 @@ fn stdin_insensitive_count() -> TestResult {
     let expected = fs::read_to_string(expected_file)?;
 
     Command::cargo_bin(PRG)?
-        .args(&["-ci", "the", "-"])
+        .args(["-ci", "the", "-"])
+        .write_stdin(input)
+        .assert()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_sorted(args: &[&str], expected: &[&str]) -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<String> = stdout.lines().map(|l| l.replace('\\', "/")).collect();
+    lines.sort();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_min_count() -> TestResult {
+    run_sorted(
+        &["-ric", "--min-count", "2", "the", INPUTS_DIR],
+        &["tests/inputs/bustle.txt:3", "tests/inputs/nobody.txt:3"],
+    )?;
+    run_sorted(
+        &["-ric", "--min-count", "1", "the", INPUTS_DIR],
+        &[
+            "tests/inputs/bustle.txt:3",
+            "tests/inputs/fox.txt:1",
+            "tests/inputs/nobody.txt:3",
+        ],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn sum() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--sum", "-i", "the", BUSTLE, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout("7\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sum_min_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            "-c",
+            "--sum",
+            "--min-count",
+            "2",
+            "-i",
+            "the",
+            BUSTLE,
+            FOX,
+            NOBODY,
+        ])
+        .assert()
+        .success()
+        .stdout("6\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_size_limit() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--size-limit", "1000", r"\w{100}", FOX])
+        .assert()
+        .failure()
+        .stderr("Pattern exceeds --size-limit of 1000 bytes\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["--size-limit", "1000000", "fox", FOX])
+        .assert()
+        .success()
+        .stdout("The quick brown fox jumps over the lazy dog.\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_size_limit() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--size-limit", "x", "fox", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --size-limit \"x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_min_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--min-count", "x", "the", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --min-count \"x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn min_count_exit_status() -> TestResult {
+    // Leaving every file out still counts as a match
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--min-count", "5", "the", FOX])
+        .assert()
+        .success()
+        .stdout("");
+
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--min-count", "1", "zebra", FOX])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_min_count_without_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--min-count", "2", "the", FOX])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_multiline() -> TestResult {
+    run(
+        &["--multiline", r"death\n.*solemn", BUSTLE],
+        "tests/expected/bustle.txt.multiline",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_no_multiline() -> TestResult {
+    run(&[r"death\n.*solemn", BUSTLE], "tests/expected/empty.foo")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_byte_offset() -> TestResult {
+    run(
+        &["-b", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.byte_offset",
+    )
+}
+
+// --------------------------------------------------
+fn run_count(args: &[&str], expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_zero_matches() -> TestResult {
+    run_count(&["-c", "xyz", FOX], "0\n")?;
+    run_count(&["-cv", "fox", FOX], "0\n")?;
+    run_count(&["-cv", "xyz", FOX], "1\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn count_empty_file() -> TestResult {
+    run_count(&["-c", "foo", EMPTY], "0\n")?;
+    run_count(&["-cv", "foo", EMPTY], "0\n")?;
+    run_count(&["-c", "", EMPTY], "0\n")?;
+    run_count(&["-c", "--multiline", "", EMPTY], "0\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn count_empty_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "foo"])
+        .write_stdin("")
+        .assert()
+        .stdout("0\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn encoding_latin1() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--encoding", "latin1", "é|ï", LATIN1])
+        .assert()
+        .success()
+        .stdout("café au lait\nnaïve\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn encoding_default_utf8() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["é", LATIN1])
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr(predicate::str::contains(
+            "stream did not contain valid UTF-8",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_encoding() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--encoding", "klingon", "x", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(r#"Invalid --encoding "klingon""#));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn null_data() -> TestResult {
+    let input = "alpha\nbeta\0gamma\0delta\nalphabet\0";
+    Command::cargo_bin(PRG)?
+        .args(["-z", "a\nb"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("alpha\nbeta\0");
+
+    Command::cargo_bin(PRG)?
+        .args(["--null-data", "-v", "-b", "gamma"])
         .write_stdin(input)
         .assert()
+        .success()
+        .stdout("0:alpha\nbeta\u{0}17:delta\nalphabet\0");
+
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-c", "alpha"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("2\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_files() -> TestResult {
+    run_success(
+        &["-r", "--sort-files", "-c", ".", INPUTS_DIR],
+        "tests/expected/sort_files.count.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files_from() -> TestResult {
+    let list = tempfile::NamedTempFile::new()?;
+    fs::write(list.path(), format!("{}\n\n{}\n", FOX, NOBODY))?;
+    let list = list.path().to_string_lossy().to_string();
+
+    Command::cargo_bin(PRG)?
+        .args(["--files-from", &list, "-c", "The"])
+        .assert()
+        .success()
+        .stdout(format!("{}:1\n{}:1\n", FOX, NOBODY));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_from_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--files-from", "-", "dog"])
+        .write_stdin(format!("{}\n", FOX))
+        .assert()
+        .success()
+        .stdout("The quick brown fox jumps over the lazy dog.\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_files_from() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--files-from", &bad, "dog"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(format!(
+            "{}: .* [(]os error 2[)]",
+            bad
+        ))?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn passthru() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--passthru", "--color=always", "fox|dog"])
+        .write_stdin("a fox\nno match here\n\nthe dog and the fox\n")
+        .assert()
+        .success()
+        .stdout(
+            "a \x1b[01;31mfox\x1b[0m\n\
+             no match here\n\
+             \n\
+             the \x1b[01;31mdog\x1b[0m and the \x1b[01;31mfox\x1b[0m\n",
+        );
+
+    Command::cargo_bin(PRG)?
+        .args(["--passthru", "fox|dog"])
+        .write_stdin("a fox\nno match here\n")
+        .assert()
+        .success()
+        .stdout("a fox\nno match here\n");
+
+    // Lines without a match are labelled like context lines
+    Command::cargo_bin(PRG)?
+        .args(["--passthru", "-n", "fox|dog"])
+        .write_stdin("a fox\nno match here\nthe dog\n")
+        .assert()
+        .success()
+        .stdout("1:a fox\n2-no match here\n3:the dog\n");
+
+    // Highlighting mustn't stop an anchored pattern matching its own line
+    Command::cargo_bin(PRG)?
+        .args(["--passthru", "--color=always", "-n", "^fox"])
+        .write_stdin("fox one\nno fox\n")
+        .assert()
+        .success()
+        .stdout("1:\x1b[01;31mfox\x1b[0m one\n2-no fox\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn passthru_no_matches() -> TestResult {
+    let expected = fs::read_to_string(BUSTLE)?;
+    Command::cargo_bin(PRG)?
+        .args(["--passthru", "zebra", BUSTLE])
+        .assert()
+        .code(1)
         .stdout(expected);
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn require_all() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let src = dir.path().join("src");
+    fs::create_dir(&src)?;
+    fs::write(
+        src.join("lib.rs"),
+        "// SPDX-License-Identifier: MIT\nmod main;\n",
+    )?;
+    fs::write(
+        src.join("main.rs"),
+        "// SPDX-License-Identifier: MIT\nfn main() {}\n",
+    )?;
+    fs::write(src.join("util.rs"), "pub fn util() {}\n")?;
+    let args = [
+        "-r",
+        "--require-all",
+        "SPDX-License-Identifier",
+        &src.to_string_lossy(),
+    ];
+
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .failure()
+        .stdout(format!("{}\n", src.join("util.rs").display()))
+        .stderr("1 of 3 files have no match for \"SPDX-License-Identifier\"\n");
+
+    // -q only hides the list; a file without a match still fails
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .arg("-q")
+        .assert()
+        .code(2)
+        .stdout("");
+
+    fs::write(src.join("util.rs"), "// SPDX-License-Identifier: MIT\n")?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout("");
+
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .arg("-q")
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn line_number() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "The", BUSTLE])
+        .assert()
+        .success()
+        .stdout(
+            "1:The bustle in a house\n2:The morning after death\n6:The sweeping up the heart,\n",
+        );
+
+    // Numbers count every line read, and follow the file name
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-b", "fox", FOX, "-"])
+        .write_stdin("no\n\nfox\n")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:1:0:The quick brown fox jumps over the lazy dog.\n-:3:4:fox\n",
+            FOX
+        ));
+
+    Command::cargo_bin(PRG)?
+        .args(["-nv", "--multiline", "e", BUSTLE])
+        .assert()
+        .success()
+        .stdout("5:\n");
+
+    // --count ignores -n
+    Command::cargo_bin(PRG)?
+        .args(["-nc", "The", BUSTLE])
+        .assert()
+        .success()
+        .stdout("3\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-o", "[a-z]*o[a-z]*", FOX])
+        .assert()
+        .success()
+        .stdout("brown\nfox\nover\ndog\n");
+
+    // Offsets are those of each match, and empty matches are skipped
+    Command::cargo_bin(PRG)?
+        .args(["-onb", "x*", FOX, "-"])
+        .write_stdin("none\nxx and x\n")
+        .assert()
+        .success()
+        .stdout(format!("{0}:1:18:x\n-:2:5:xx\n-:2:12:x\n", FOX));
+
+    Command::cargo_bin(PRG)?
+        .args(["-o", "-v", "fox", FOX])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn context() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-A", "1", "house|Enacted|eternity", BUSTLE])
+        .assert()
+        .success()
+        .stdout(
+            "1:The bustle in a house\n2-The morning after death\n--\n\
+             4:Enacted upon earth,—\n5-\n--\n9:Until eternity.\n",
+        );
+
+    Command::cargo_bin(PRG)?
+        .args(["-B1", "morning|love", BUSTLE])
+        .assert()
+        .success()
+        .stdout(
+            "The bustle in a house\nThe morning after death\n--\n\
+             The sweeping up the heart,\nAnd putting love away\n",
+        );
+
+    // Overlapping context is printed once; -B overrides -C
+    Command::cargo_bin(PRG)?
+        .args(["-C", "2", "-B", "0", "-n", "morning|Is", BUSTLE])
+        .assert()
+        .success()
+        .stdout(
+            "2:The morning after death\n3:Is solemnest of industries\n4-Enacted upon earth,—\n5-\n",
+        );
+
+    // Groups from different files are separated too
+    Command::cargo_bin(PRG)?
+        .args(["-C1", "fox", FOX, "-"])
+        .write_stdin("a\nfox\nb\n")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{0}:The quick brown fox jumps over the lazy dog.\n--\n--a\n-:fox\n--b\n",
+            FOX
+        ));
+
+    // Counts ignore context
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-C3", "The", BUSTLE])
+        .assert()
+        .success()
+        .stdout("3\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_context() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-A", "x", "fox", FOX])
+        .assert()
+        .failure()
+        .stderr("Invalid -A \"x\"\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-C1", "-o", "fox", FOX])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_patterns() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-e", "house", "-e", "love", BUSTLE])
+        .assert()
+        .success()
+        .stdout("The bustle in a house\nAnd putting love away\n");
+
+    // The first positional argument is a file once -e is given
+    Command::cargo_bin(PRG)?
+        .args(["-i", "-e", "HOUSE", "--regexp", "-LAZY", BUSTLE, FOX])
+        .assert()
+        .success()
+        .stdout(format!("{}:The bustle in a house\n", BUSTLE));
+
+    Command::cargo_bin(PRG)?
+        .args(["-e", "b", "-e", "d"])
+        .write_stdin("a\nb\nc\nd\n")
+        .assert()
+        .success()
+        .stdout("b\nd\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn pattern_file() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let patterns = dir.path().join("patterns");
+    fs::write(&patterns, "morning\neternity\n")?;
+    let patterns = patterns.to_string_lossy();
+
+    Command::cargo_bin(PRG)?
+        .args(["-f", &patterns, "-e", "^Is", BUSTLE])
+        .assert()
+        .success()
+        .stdout("The morning after death\nIs solemnest of industries\nUntil eternity.\n");
+
+    // An empty pattern file matches nothing
+    let empty = dir.path().join("empty");
+    fs::write(&empty, "")?;
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-f", &empty.to_string_lossy(), BUSTLE])
+        .assert()
+        .code(1)
+        .stdout("0\n");
+
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-f", &bad, BUSTLE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(format!(
+            "{}: .* [(]os error 2[)]",
+            bad
+        ))?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_strings() -> TestResult {
+    let input = "a.b\naxb\n(a+b)\nA.B\n";
+    Command::cargo_bin(PRG)?
+        .args(["-F", "a.b"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("a.b\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["--fixed-strings", "-i", "-e", "a.b", "-e", "(a+"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("a.b\n(a+b)\nA.B\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["a.b"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("a.b\naxb\n(a+b)\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn word_and_line_regexp() -> TestResult {
+    let input = "cat\ncategory\nthe cat sat\nbobcat\nCAT\n";
+    Command::cargo_bin(PRG)?
+        .args(["-w", "cat"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("cat\nthe cat sat\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-wiv", "cat"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("category\nbobcat\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-x", "-i", "-e", "cat", "-e", "bob"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("cat\nCAT\n");
+
+    // Alternation stays inside the anchors
+    Command::cargo_bin(PRG)?
+        .args(["-x", "cat|bobcat"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("cat\nbobcat\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ascii_boundaries() -> TestResult {
+    // "é" is a word character to Unicode but not to ASCII
+    let input = "caféfoo\ncafé foo\n";
+    Command::cargo_bin(PRG)?
+        .args(["-w", "foo"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("café foo\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-w", "--ascii-boundaries", "foo"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(input);
+
+    Command::cargo_bin(PRG)?
+        .args(["--ascii-boundaries", "foo"])
+        .write_stdin(input)
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-m", "2", "The", BUSTLE])
+        .assert()
+        .success()
+        .stdout("The bustle in a house\nThe morning after death\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--max-count=2", "The", BUSTLE])
+        .assert()
+        .success()
+        .stdout("2\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-m1", "-A1", "-n", "The", BUSTLE])
+        .assert()
+        .success()
+        .stdout("1:The bustle in a house\n2-The morning after death\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-m", "x", "The", BUSTLE])
+        .assert()
+        .failure()
+        .stderr("Invalid --max-count \"x\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exit_status() -> TestResult {
+    Command::cargo_bin(PRG)?.args(["fox", FOX]).assert().code(0);
+
+    Command::cargo_bin(PRG)?
+        .args(["zebra", FOX])
+        .assert()
+        .code(1)
+        .stdout("");
+
+    Command::cargo_bin(PRG)?
+        .args(["-c", "zebra", FOX])
+        .assert()
+        .code(1)
+        .stdout("0\n");
+
+    // Context lines alone aren't a match
+    Command::cargo_bin(PRG)?
+        .args(["-v", "-A1", "", FOX])
+        .assert()
+        .code(1);
+
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["fox", FOX, &bad])
+        .assert()
+        .code(2)
+        .stdout(format!(
+            "{}:The quick brown fox jumps over the lazy dog.\n",
+            FOX
+        ));
+
+    Command::cargo_bin(PRG)?
+        .args(["*foo", FOX])
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-q", "The", BUSTLE, FOX])
+        .assert()
+        .code(0)
+        .stdout("");
+
+    Command::cargo_bin(PRG)?
+        .args(["--quiet", "-c", "zebra", BUSTLE])
+        .assert()
+        .code(1)
+        .stdout("");
+
+    Command::cargo_bin(PRG)?
+        .args(["-q", "--passthru", "Until", BUSTLE])
+        .assert()
+        .code(0)
+        .stdout("");
+
+    // The first match ends the search before the missing file is read
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-q", "fox", FOX, &bad])
+        .assert()
+        .code(0)
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--color=always", "-n", "fox|dog", FOX, "-"])
+        .write_stdin("no match\nfox\n")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:1:The quick brown \x1b[01;31mfox\x1b[0m jumps over the lazy \x1b[01;31mdog\x1b[0m.\n\
+             -:2:\x1b[01;31mfox\x1b[0m\n",
+            FOX
+        ));
+
+    Command::cargo_bin(PRG)?
+        .args(["--color=always", "-o", "o[a-z]"])
+        .write_stdin("brown fox\n")
+        .assert()
+        .success()
+        .stdout("\x1b[01;31mow\x1b[0m\n\x1b[01;31mox\x1b[0m\n");
+
+    // Context lines are left alone
+    Command::cargo_bin(PRG)?
+        .args(["--color=always", "-v", "-B1", "fox"])
+        .write_stdin("fox\nthe dog\n")
+        .assert()
+        .success()
+        .stdout("fox\nthe dog\n");
+
+    // Output to a pipe is never a terminal
+    for args in [
+        &["fox", FOX][..],
+        &["--color", "fox", FOX],
+        &["--color=never", "fox", FOX],
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .assert()
+            .success()
+            .stdout("The quick brown fox jumps over the lazy dog.\n");
+    }
+
+    Command::cargo_bin(PRG)?
+        .args(["--color=sometimes", "fox", FOX])
+        .assert()
+        .failure();
+    Ok(())
+}
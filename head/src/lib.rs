@@ -1,15 +1,36 @@
 use clap::{App, Arg};
+use common::{humanize_bytes, incomplete_tail};
+use std::collections::VecDeque;
 use std::error::Error;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+// Multipliers for count suffixes, as in GNU head
+const SUFFIXES: [(char, usize); 7] = [
+    ('b', 512),
+    ('k', 1 << 10),
+    ('K', 1 << 10),
+    ('m', 1 << 20),
+    ('M', 1 << 20),
+    ('g', 1 << 30),
+    ('G', 1 << 30),
+];
+
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
-    lines: usize,
-    bytes: Option<usize>,
+    // Negative counts print all but the last lines
+    lines: i64,
+    bytes: Option<i64>,
+    preview: Option<(usize, usize)>,
+    hex: bool,
+    preserve_chars: bool,
+    report_size: bool,
+    quiet: bool,
+    verbose: bool,
+    zero_terminated: bool,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -25,7 +46,8 @@ pub fn get_args() -> MyResult<Config> {
                 .number_of_values(1)
                 .takes_value(true)
                 .value_name("LINES")
-                .help("Number of lines"),
+                .allow_hyphen_values(true)
+                .help("Number of lines, or with a leading - all but the last LINES"),
         )
         .arg(
             Arg::with_name("bytes")
@@ -35,7 +57,40 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(true)
                 .conflicts_with("lines")
                 .value_name("BYTES")
-                .help("Number of bytes"),
+                .allow_hyphen_values(true)
+                .help("Number of bytes, or with a leading - all but the last BYTES"),
+        )
+        .arg(
+            Arg::with_name("hex")
+                .long("hex")
+                .takes_value(false)
+                .requires("bytes")
+                .help("Print the bytes as a hexdump -C style listing"),
+        )
+        .arg(
+            Arg::with_name("preserve_chars")
+                .long("preserve-chars")
+                .takes_value(false)
+                .requires("bytes")
+                .conflicts_with("hex")
+                .help("Drop a UTF-8 character cut off at the end of the bytes"),
+        )
+        .arg(
+            Arg::with_name("head")
+                .long("head")
+                .takes_value(true)
+                .value_name("N")
+                .requires("tail")
+                .conflicts_with_all(&["lines", "bytes"])
+                .help("Preview the first N lines (use with --tail)"),
+        )
+        .arg(
+            Arg::with_name("tail")
+                .long("tail")
+                .takes_value(true)
+                .value_name("M")
+                .requires("head")
+                .help("Preview the last M lines (use with --head)"),
         )
         .arg(
             Arg::with_name("files")
@@ -45,57 +100,144 @@ pub fn get_args() -> MyResult<Config> {
                 .value_name("FILE")
                 .help("File name to be read"),
         )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .takes_value(false)
+                .help("Never print headers"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .takes_value(false)
+                .conflicts_with("quiet")
+                .help("Always print headers, even for a single file"),
+        )
+        .arg(
+            Arg::with_name("zero_terminated")
+                .short("z")
+                .long("zero-terminated")
+                .takes_value(false)
+                .conflicts_with("head")
+                .help("Lines end in NUL, not newline; bytes between NULs pass through as is"),
+        )
+        .arg(
+            Arg::with_name("report_size")
+                .long("report-size")
+                .takes_value(false)
+                .help("Report the number of bytes printed to stderr"),
+        )
         .get_matches();
 
     let lines = matches
         .value_of("lines")
-        .map(parse_positive_int)
+        .map(parse_signed_int)
         .transpose()
         .map_err(|e| format!("illegal line count -- {}", e))?;
 
     let bytes = matches
         .value_of("bytes")
-        .map(parse_positive_int)
+        .map(parse_signed_int)
         .transpose()
         .map_err(|e| format!("illegal byte count -- {}", e))?;
 
+    let preview = match (matches.value_of("head"), matches.value_of("tail")) {
+        (Some(head), Some(tail)) => Some((
+            parse_positive_int(head).map_err(|e| format!("illegal --head count -- {}", e))?,
+            parse_positive_int(tail).map_err(|e| format!("illegal --tail count -- {}", e))?,
+        )),
+        _ => None,
+    };
+
     Ok(Config {
         files: matches.values_of_lossy("files").unwrap(),
         lines: lines.unwrap(),
         bytes,
+        preview,
+        hex: matches.is_present("hex"),
+        preserve_chars: matches.is_present("preserve_chars"),
+        report_size: matches.is_present("report_size"),
+        quiet: matches.is_present("quiet"),
+        verbose: matches.is_present("verbose"),
+        zero_terminated: matches.is_present("zero_terminated"),
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
     let len = config.files.len();    
-    let is_not_len_1: bool = len > 1;
+    let headers = config.verbose || (!config.quiet && len > 1);
+    let mut printed = 0;
+    let mut failed = 0;
 
     for (i, filename) in config.files.into_iter().enumerate() {
         match open(&filename) {
-            Err(e) => eprintln!("Failed to open {}: {}", filename, e),
+            Err(e) => {
+                eprintln!("Failed to open {}: {}", filename, e);
+                failed += 1;
+            }
             Ok(file) => {   
-                if is_not_len_1 {
-                    println!("==> {} <==", filename);
+                if headers {
+                    println!("==> {} <==", display_name(&filename));
                 }
 
-                match config.bytes {
-                    Some(c) => print_bytes(file, c)?,
-                    None => print_lines(file, config.lines)?,
+                printed += match (config.bytes, config.preview) {
+                    (Some(c), _) => {
+                        let size = file_size(&filename);
+                        print_bytes(file, c, size, config.hex, config.preserve_chars)?
+                    }
+                    (None, Some((head, tail))) => print_preview(file, head, tail)?,
+                    (None, None) => {
+                        let terminator = if config.zero_terminated { b'\0' } else { b'\n' };
+                        print_lines(file, config.lines, terminator)?
+                    }
                 };
 
-                if is_not_len_1 && i+1 < len {
-                    println!("");
+                if headers && i+1 < len {
+                    println!();
                 }
             }
         }
     }
 
+    if config.report_size {
+        eprintln!("printed {}", humanize_bytes(printed));
+    }
+
+    // Report the failures once more, after all output, and exit non-zero
+    if failed > 0 {
+        return Err(From::from(format!("failed to open {} of {} files", failed, len)));
+    }
     Ok(())
 }
 
+fn display_name(filename: &str) -> &str {
+    match filename {
+        "-" => "standard input",
+        _ => filename,
+    }
+}
+
 fn parse_positive_int(val: &str) -> MyResult<usize> {
-    match val.parse() {
-        Ok(n) if n > 0 => Ok(n),
+    let (digits, multiplier) = SUFFIXES
+        .iter()
+        .find_map(|&(suffix, m)| val.strip_suffix(suffix).map(|d| (d, m)))
+        .unwrap_or((val, 1));
+    match digits.parse::<usize>().ok().and_then(|n| n.checked_mul(multiplier)) {
+        Some(n) if n > 0 => Ok(n),
+        _ => Err(From::from(val)),
+    }
+}
+
+// A leading "-" negates an otherwise positive count
+fn parse_signed_int(val: &str) -> MyResult<i64> {
+    let (sign, digits) = match val.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, val),
+    };
+    match parse_positive_int(digits).map(i64::try_from) {
+        Ok(Ok(n)) => Ok(sign * n),
         _ => Err(From::from(val)),
     }
 }
@@ -107,24 +249,175 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-fn print_bytes(file: Box<dyn BufRead>,byte_count: usize) -> MyResult<()> {
-    let mut handle = file.take(TryFrom::try_from(byte_count)?);
-    let mut buffer = vec![0;byte_count];
-    let n = handle.read(&mut buffer)?;
+// Size of a regular file; None for standard input, pipes and the like
+fn file_size(filename: &str) -> Option<u64> {
+    match filename {
+        "-" => None,
+        _ => fs::metadata(filename).ok().filter(|m| m.is_file()).map(|m| m.len()),
+    }
+}
 
-    print!("{}", String::from_utf8_lossy(&buffer[..n]));
+// A negative count needs to know where the input ends: a known size says
+// so up front, anything else is read through a ring of the last bytes
+fn print_bytes(
+    file: Box<dyn BufRead>,
+    byte_count: i64,
+    size: Option<u64>,
+    hex: bool,
+    preserve_chars: bool,
+) -> MyResult<u64> {
+    let skip = byte_count.unsigned_abs();
+    let mut buffer = match (byte_count < 0, size) {
+        (false, _) => read_at_most(file, skip)?,
+        (true, Some(size)) => read_at_most(file, size.saturating_sub(skip))?,
+        (true, None) => read_all_but_last_bytes(file, skip as usize)?,
+    };
+    if preserve_chars {
+        buffer.truncate(buffer.len() - incomplete_tail(&buffer));
+    }
 
-    Ok(())
+    if hex {
+        print!("{}", format_hexdump(&buffer));
+    } else {
+        print!("{}", String::from_utf8_lossy(&buffer));
+    }
+
+    Ok(buffer.len() as u64)
+}
+
+fn read_at_most(file: Box<dyn BufRead>, limit: u64) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    file.take(limit).read_to_end(&mut buffer)?;
+    Ok(buffer)
 }
 
-fn print_lines(mut file: Box<dyn BufRead>, line_count: usize) -> MyResult<()> {
-    let mut buffer = String::new();
+fn read_all_but_last_bytes(mut file: Box<dyn BufRead>, skip: usize) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let mut ring: VecDeque<u8> = VecDeque::with_capacity(skip);
+    loop {
+        let chunk = file.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        let n = chunk.len();
+        ring.extend(chunk);
+        file.consume(n);
+        let excess = ring.len().saturating_sub(skip);
+        buffer.extend(ring.drain(..excess));
+    }
+    Ok(buffer)
+}
+
+// Lines are handled as raw bytes, so any encoding passes through untouched
+fn print_lines(mut file: Box<dyn BufRead>, line_count: i64, terminator: u8) -> MyResult<u64> {
+    if line_count < 0 {
+        return print_all_but_last_lines(file, line_count.unsigned_abs() as usize, terminator);
+    }
+
+    let mut out = io::stdout().lock();
+    let mut line = Vec::new();
+    let mut printed = 0;
     for _ in 0..line_count {
-        file.read_line(&mut buffer)?;
+        line.clear();
+        if file.read_until(terminator, &mut line)? == 0 {
+            break;
+        }
+        out.write_all(&line)?;
+        printed += line.len() as u64;
     }
-    print!("{}", buffer);
 
-    Ok(())
+    Ok(printed)
+}
+
+// Holds back the most recent `skip` lines, printing each older one as it
+// leaves the ring; whatever is still held at the end is dropped
+fn print_all_but_last_lines(
+    mut file: Box<dyn BufRead>,
+    skip: usize,
+    terminator: u8,
+) -> MyResult<u64> {
+    let mut out = io::stdout().lock();
+    let mut printed = 0;
+    let mut ring: VecDeque<Vec<u8>> = VecDeque::with_capacity(skip + 1);
+    let mut line = Vec::new();
+    while file.read_until(terminator, &mut line)? > 0 {
+        ring.push_back(std::mem::take(&mut line));
+        if ring.len() > skip {
+            let oldest = ring.pop_front().unwrap();
+            out.write_all(&oldest)?;
+            printed += oldest.len() as u64;
+        }
+    }
+
+    Ok(printed)
+}
+
+/// Formats bytes like `hexdump -C`: an offset, sixteen hex bytes split in two
+/// groups of eight, and an ASCII gutter, followed by a line with the total length.
+fn format_hexdump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        out.push_str(&format!("{:08x}  ", i * 16));
+        for j in 0..16 {
+            match chunk.get(j) {
+                Some(b) => out.push_str(&format!("{:02x} ", b)),
+                None => out.push_str("   "),
+            }
+            if j == 7 {
+                out.push(' ');
+            }
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' })
+            .collect();
+        out.push_str(&format!(" |{}|\n", ascii));
+    }
+    if !bytes.is_empty() {
+        out.push_str(&format!("{:08x}\n", bytes.len()));
+    }
+    out
+}
+
+/// Separator printed between the head and tail of a preview when lines were
+/// skipped.
+const PREVIEW_SEPARATOR: &str = "...";
+
+// Prints the first `head` and last `tail` lines, keeping only the tail in memory.
+// When the file has no more than head + tail lines it is printed whole.
+fn print_preview(mut file: Box<dyn BufRead>, head: usize, tail: usize) -> MyResult<u64> {
+    let mut printed = 0;
+    let mut line = String::new();
+
+    for _ in 0..head {
+        if file.read_line(&mut line)? == 0 {
+            break;
+        }
+        print!("{}", line);
+        printed += line.len() as u64;
+        line.clear();
+    }
+
+    let mut ring: VecDeque<String> = VecDeque::with_capacity(tail);
+    let mut skipped = false;
+    while file.read_line(&mut line)? > 0 {
+        if ring.len() == tail {
+            ring.pop_front();
+            skipped = true;
+        }
+        ring.push_back(std::mem::take(&mut line));
+    }
+
+    if skipped {
+        println!("{}", PREVIEW_SEPARATOR);
+        printed += PREVIEW_SEPARATOR.len() as u64 + 1;
+    }
+    for line in ring {
+        print!("{}", line);
+        printed += line.len() as u64;
+    }
+
+    Ok(printed)
 }
 
 #[test]
@@ -140,4 +433,40 @@ fn test_parse_positive_int() {
     let res = parse_positive_int("0");
     assert!(res.is_err());
     assert_eq!(res.unwrap_err().to_string(), "0".to_string());
+
+    assert_eq!(parse_positive_int("2b").unwrap(), 1024);
+    assert_eq!(parse_positive_int("16K").unwrap(), 16 * 1024);
+    assert_eq!(parse_positive_int("1k").unwrap(), 1024);
+    assert_eq!(parse_positive_int("3M").unwrap(), 3 * 1024 * 1024);
+    assert_eq!(parse_positive_int("1g").unwrap(), 1024 * 1024 * 1024);
+
+    for bad in ["K", "0K", "3Q", "1KB", "K1"] {
+        let res = parse_positive_int(bad);
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), bad.to_string());
+    }
+}
+
+#[test]
+fn test_parse_signed_int() {
+    assert_eq!(parse_signed_int("3").unwrap(), 3);
+    assert_eq!(parse_signed_int("-3").unwrap(), -3);
+    assert_eq!(parse_signed_int("-1K").unwrap(), -1024);
+
+    for bad in ["-0", "0", "--3", "-", "-foo"] {
+        let res = parse_signed_int(bad);
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), bad.to_string());
+    }
+}
+
+#[test]
+fn test_format_hexdump() {
+    assert_eq!(format_hexdump(b""), "");
+    assert_eq!(
+        format_hexdump(b"Hello, world!\n\x00\x01\xff"),
+        "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 01  |Hello, world!...|\n\
+         00000010  ff                                                |.|\n\
+         00000011\n"
+    );
 }
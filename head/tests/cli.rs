@@ -41,7 +41,7 @@ fn dies_bad_bytes() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal byte count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-c", &bad, EMPTY])
+        .args(["-c", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -55,7 +55,7 @@ fn dies_bad_lines() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal line count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-n", &bad, EMPTY])
+        .args(["-n", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -70,7 +70,7 @@ fn dies_bytes_and_lines() -> TestResult {
                used with '--bytes <BYTES>'";
 
     Command::cargo_bin(PRG)?
-        .args(&["-n", "1", "-c", "2"])
+        .args(["-n", "1", "-c", "2"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(msg));
@@ -91,6 +91,21 @@ fn skips_bad_file() -> TestResult {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn bad_file_exits_nonzero() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args([ONE, &bad])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains("==> ./tests/inputs/one.txt <=="))
+        .stderr(predicate::str::ends_with("failed to open 1 of 2 files\n"));
+
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> TestResult {
     // Extra work here due to lossy UTF
@@ -103,7 +118,7 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
         .args(args)
         .assert()
         .success()
-        .stdout(predicate::eq(&expected.as_bytes() as &[u8]));
+        .stdout(predicate::eq(expected.as_bytes() as &[u8]));
 
     Ok(())
 }
@@ -121,7 +136,7 @@ fn run_stdin(args: &[&str], input_file: &str, expected_file: &str) -> TestResult
         .write_stdin(input)
         .args(args)
         .assert()
-        .stdout(predicate::eq(&expected.as_bytes() as &[u8]));
+        .stdout(predicate::eq(expected.as_bytes() as &[u8]));
 
     Ok(())
 }
@@ -415,3 +430,280 @@ fn multiple_files_c4() -> TestResult {
         "tests/expected/all.c4.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn report_size() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--report-size", "-c", "4", TEN])
+        .assert()
+        .success()
+        .stdout("one\n")
+        .stderr("printed 4 B\n");
+    Ok(())
+}
+
+#[test]
+fn report_size_multiple_files() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--report-size", "-n", "1", ONE, TEN])
+        .assert()
+        .success()
+        .stderr("printed 27 B\n");
+    Ok(())
+}
+
+#[test]
+fn preview_head_tail() -> TestResult {
+    run(
+        &[TEN, "--head", "2", "--tail", "3"],
+        "tests/expected/ten.txt.head2.tail3.out",
+    )
+}
+
+#[test]
+fn preview_head_tail_stdin() -> TestResult {
+    run_stdin(
+        &["--head", "2", "--tail", "3"],
+        TEN,
+        "tests/expected/ten.txt.head2.tail3.out",
+    )
+}
+
+#[test]
+fn preview_covers_whole_file() -> TestResult {
+    // head + tail equal to the line count prints the file with no separator
+    run(
+        &[THREE, "--head", "2", "--tail", "1"],
+        "tests/expected/three.txt.head2.tail1.out",
+    )?;
+    run(&[THREE, "--head", "5", "--tail", "5"], "tests/expected/three.txt.out")
+}
+
+#[test]
+fn dies_head_without_tail() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--head", "2", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--tail <M>"));
+    Ok(())
+}
+
+#[test]
+fn dies_bad_preview_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--head", "2", "--tail", "0", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal --tail count -- 0"));
+    Ok(())
+}
+
+#[test]
+fn hex_c30() -> TestResult {
+    run(
+        &[TEN, "--hex", "-c", "30"],
+        "tests/expected/ten.txt.hex.c30.out",
+    )
+}
+
+#[test]
+fn dies_hex_without_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--hex", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--bytes <BYTES>"));
+    Ok(())
+}
+
+#[test]
+fn stdin_header() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", ONE, "-"])
+        .write_stdin("x\ny\n")
+        .assert()
+        .success()
+        .stdout("==> ./tests/inputs/one.txt <==\nÖne line, four words.\n\n==> standard input <==\nx\n");
+    Ok(())
+}
+
+#[test]
+fn negative_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-7", TEN])
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-10", TEN])
+        .assert()
+        .success()
+        .stdout("");
+
+    Command::cargo_bin(PRG)?
+        .args(["--lines=-1", "-"])
+        .write_stdin("a\nb\nc")
+        .assert()
+        .success()
+        .stdout("a\nb\n");
+    Ok(())
+}
+
+#[test]
+fn negative_bytes_file() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-40", TEN])
+        .assert()
+        .success()
+        .stdout("one\ntwo\nt");
+
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-100", TEN])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn negative_bytes_stdin() -> TestResult {
+    let input = "x".repeat(20_000) + "tail";
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-4"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("x".repeat(20_000));
+
+    Command::cargo_bin(PRG)?
+        .args(["--bytes=-10", "-"])
+        .write_stdin("short")
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn size_suffixes() -> TestResult {
+    let input = "x".repeat(3000);
+    Command::cargo_bin(PRG)?
+        .args(["-c", "2K"])
+        .write_stdin(input.as_str())
+        .assert()
+        .success()
+        .stdout("x".repeat(2048));
+
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-5b"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("x".repeat(3000 - 2560));
+    Ok(())
+}
+
+#[test]
+fn dies_bad_suffix() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "3Q", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal line count -- 3Q"));
+    Ok(())
+}
+
+#[test]
+fn quiet_multiple_files() -> TestResult {
+    for flag in ["-q", "--quiet"] {
+        Command::cargo_bin(PRG)?
+            .args([flag, "-n", "1", ONE, TEN])
+            .assert()
+            .success()
+            .stdout("Öne line, four words.\none\n");
+    }
+    Ok(())
+}
+
+#[test]
+fn verbose_single_file() -> TestResult {
+    for flag in ["-v", "--verbose"] {
+        Command::cargo_bin(PRG)?
+            .args([flag, "-n", "1", TEN])
+            .assert()
+            .success()
+            .stdout("==> ./tests/inputs/ten.txt <==\none\n");
+    }
+    Ok(())
+}
+
+#[test]
+fn dies_quiet_and_verbose() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-q", "-v", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn preserve_chars() -> TestResult {
+    // "€" is three bytes, so three bytes end partway through it
+    Command::cargo_bin(PRG)?
+        .args(["-c", "3", "--preserve-chars"])
+        .write_stdin("ab€c")
+        .assert()
+        .success()
+        .stdout("ab");
+
+    Command::cargo_bin(PRG)?
+        .args(["-c", "3"])
+        .write_stdin("ab€c")
+        .assert()
+        .success()
+        .stdout("ab\u{FFFD}");
+
+    Command::cargo_bin(PRG)?
+        .args(["-c", "5", "--preserve-chars"])
+        .write_stdin("ab€c")
+        .assert()
+        .success()
+        .stdout("ab€");
+    Ok(())
+}
+
+#[test]
+fn zero_terminated() -> TestResult {
+    let input: &[u8] = b"one\nline\0two\xff\0three\0four";
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-n", "2"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(predicate::eq(b"one\nline\0two\xff\0" as &[u8]));
+
+    Command::cargo_bin(PRG)?
+        .args(["--zero-terminated", "-n", "-1"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(predicate::eq(b"one\nline\0two\xff\0three\0" as &[u8]));
+    Ok(())
+}
+
+#[test]
+fn bad_file_between_good_files() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-q", "-n", "1", ONE, &bad, TEN])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("Öne line, four words.\none\n")
+        .stderr(predicate::str::ends_with("failed to open 1 of 3 files\n"));
+    Ok(())
+}
@@ -1,18 +1,26 @@
 use clap::{App, Arg};
+use common::{humanize_bytes, incomplete_tail};
 use once_cell::sync::OnceCell;
 use regex::Regex;
 use std::{
     error::Error,
-    fs::File,
-    io::{BufRead, BufReader, Read, Seek},
-    ops::Mul,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    thread,
+    time::Duration,
 };
 
-static PLUS_ZERO_REG: OnceCell<Regex> = OnceCell::new();
-static PLUS_NUM_REG: OnceCell<Regex> = OnceCell::new();
+static COUNT_REG: OnceCell<Regex> = OnceCell::new();
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+const CHUNK_SIZE: usize = 64 * 1024;
+// How long -f waits between checks for appended data
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+// How many polls without new data -F waits before checking the name still
+// refers to the file it has open, as in GNU tail
+const MAX_UNCHANGED_STATS: u32 = 5;
+
 #[derive(Debug, PartialEq)]
 enum TakeValue {
     PlusZero,
@@ -25,6 +33,25 @@ pub struct Config {
     lines: TakeValue,
     bytes: Option<TakeValue>,
     quiet: bool,
+    verbose: bool,
+    report_size: bool,
+    from_last_match: Option<Regex>,
+    follow: bool,
+    pid: Option<i32>,
+    retry: bool,
+    max_unchanged_stats: u32,
+}
+
+// A file being watched by -f. It is opened by name when there is no handle;
+// with -F the name is checked again after enough polls without new data.
+#[derive(Debug)]
+struct Followed {
+    index: usize,
+    filename: String,
+    file: Option<File>,
+    offset: u64,
+    missing: bool,
+    unchanged: u32,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -63,6 +90,58 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false)
                 .help("Suppress headers"),
         )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .takes_value(false)
+                .conflicts_with("quiet")
+                .help("Always print headers, even for a single file"),
+        )
+        .arg(
+            Arg::with_name("report_size")
+                .long("report-size")
+                .takes_value(false)
+                .help("Report the number of bytes printed to stderr"),
+        )
+        .arg(
+            Arg::with_name("from_last_match")
+                .long("from-last-match")
+                .value_name("PATTERN")
+                .conflicts_with_all(&["bytes", "lines"])
+                .help("Print from the last line matching PATTERN to the end"),
+        )
+        .arg(
+            Arg::with_name("follow")
+                .short("f")
+                .long("follow")
+                .takes_value(false)
+                .help("Keep printing data as it is appended"),
+        )
+        .arg(
+            Arg::with_name("retry")
+                .long("retry")
+                .takes_value(false)
+                .help("With -f, keep trying to open files that are missing"),
+        )
+        .arg(
+            Arg::with_name("follow_retry")
+                .short("F")
+                .takes_value(false)
+                .help("Same as --follow --retry"),
+        )
+        .arg(
+            Arg::with_name("pid")
+                .long("pid")
+                .value_name("PID")
+                .help("With -f, stop once process PID has exited"),
+        )
+        .arg(
+            Arg::with_name("max_unchanged_stats")
+                .long("max-unchanged-stats")
+                .value_name("N")
+                .help("With -F, check a file was not replaced after N polls without new data [default: 5]"),
+        )
         .get_matches();
 
     let lines = if let Some(l) = matches.value_of("lines") {
@@ -76,89 +155,406 @@ pub fn get_args() -> MyResult<Config> {
         None
     };
 
+    let pid = matches
+        .value_of("pid")
+        .map(|p| match p.parse::<i32>() {
+            Ok(pid) if pid > 0 => Ok(pid),
+            _ => Err(format!("Invalid --pid \"{}\"", p)),
+        })
+        .transpose()?;
+    let follow_retry = matches.is_present("follow_retry");
+    let follow = follow_retry || matches.is_present("follow");
+    if pid.is_some() && !follow {
+        eprintln!("warning: --pid is only used with --follow and will be ignored");
+    }
+    if matches.is_present("retry") && !follow {
+        eprintln!("warning: --retry is only used with --follow and will be ignored");
+    }
+    let max_unchanged_stats = match matches.value_of("max_unchanged_stats") {
+        Some(n) => match n.parse::<u32>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                return Err(From::from(format!(
+                    "Invalid --max-unchanged-stats \"{}\"",
+                    n
+                )))
+            }
+        },
+        None => MAX_UNCHANGED_STATS,
+    };
+    let retry = follow_retry || matches.is_present("retry");
+    if matches.is_present("max_unchanged_stats") && !(follow && retry) {
+        eprintln!("warning: --max-unchanged-stats is only used with -F and will be ignored");
+    }
+
     Ok(Config {
         files: matches.values_of_lossy("files").unwrap(),
         lines,
         bytes,
         quiet: matches.is_present("quiet"),
+        verbose: matches.is_present("verbose"),
+        report_size: matches.is_present("report_size"),
+        from_last_match: matches
+            .value_of("from_last_match")
+            .map(|p| Regex::new(p).map_err(|_| format!("Invalid --from-last-match \"{}\"", p)))
+            .transpose()?,
+        follow,
+        pid,
+        retry,
+        max_unchanged_stats,
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
     let file_count = config.files.len();
+    let headers = config.verbose || (!config.quiet && file_count > 1);
+    let mut printed = 0;
+    let mut followed = vec![];
     for (i, filename) in config.files.iter().enumerate() {
-        match File::open(&filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(file) => {
-                if !config.quiet && file_count > 1 {
-                    println!("{}==> {} <==", if i > 0 { "\n" } else { "" }, filename);
+        match open(filename) {
+            Err(err) => {
+                eprintln!("{}: {}", filename, err);
+                // Picked up by follow once it can be opened
+                if config.follow && config.retry && filename != "-" {
+                    followed.push(Followed {
+                        index: i,
+                        filename: filename.to_string(),
+                        file: None,
+                        offset: 0,
+                        missing: true,
+                        unchanged: 0,
+                    });
+                }
+            }
+            Ok(mut file) => {
+                if headers {
+                    println!(
+                        "{}==> {} <==",
+                        if i > 0 { "\n" } else { "" },
+                        display_name(filename)
+                    );
                 }
-                let (total_lines, total_bytes) = count_lines_bytes(&filename)?;
-                if let Some(b) = &config.bytes {
-                    print_bytes(&file, b, total_bytes)?;
+                let (total_lines, total_bytes) = count_lines_bytes(&mut file)?;
+                printed += if let Some(pattern) = &config.from_last_match {
+                    print_from_last_match(&mut file, pattern, total_bytes)?
+                } else if let Some(b) = &config.bytes {
+                    print_bytes(&mut file, b, total_bytes)?
                 } else {
-                    print_lines(BufReader::new(&file), &config.lines, total_lines)?;
+                    let snapshot = file.take(total_bytes as u64);
+                    print_lines(BufReader::new(snapshot), &config.lines, total_lines)?
+                };
+                // Standard input has been read to the end already
+                if config.follow && filename != "-" {
+                    followed.push(Followed {
+                        index: i,
+                        filename: filename.to_string(),
+                        file: None,
+                        offset: total_bytes as u64,
+                        missing: false,
+                        unchanged: 0,
+                    });
                 }
             }
         }
     }
+    if !followed.is_empty() {
+        follow(
+            &mut followed,
+            headers,
+            file_count - 1,
+            config.pid,
+            // Only -F follows the name rather than the open file
+            config.retry.then_some(config.max_unchanged_stats),
+        )?;
+    }
+    if config.report_size {
+        eprintln!("printed {}", humanize_bytes(printed));
+    }
     Ok(())
 }
 
+trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+// Counting needs to seek, so stdin is read into memory up front
+fn open(filename: &str) -> MyResult<Box<dyn ReadSeek>> {
+    match filename {
+        "-" => {
+            let mut buf = Vec::new();
+            io::stdin().read_to_end(&mut buf)?;
+            Ok(Box::new(Cursor::new(buf)))
+        }
+        _ => Ok(Box::new(File::open(filename)?)),
+    }
+}
+
+// Polls each file for data past its offset and prints it, with a header
+// whenever output switches to a different file. A file that shrinks is
+// assumed to have been truncated and is printed again from the start.
+// Given `max_unchanged_stats`, after that many polls without new data a
+// file whose name now refers to another file, as after log rotation, is
+// reopened and printed from the start.
+// With a pid, returns after the first poll that began once it had exited,
+// so anything it wrote before exiting is still printed.
+fn follow(
+    files: &mut [Followed],
+    headers: bool,
+    mut last_index: usize,
+    pid: Option<i32>,
+    max_unchanged_stats: Option<u32>,
+) -> MyResult<()> {
+    loop {
+        io::stdout().flush()?;
+        thread::sleep(FOLLOW_INTERVAL);
+        let exited = pid.is_some_and(|pid| !process_alive(pid));
+        for followed in files.iter_mut() {
+            let file = match &mut followed.file {
+                Some(file) => file,
+                None => match File::open(&followed.filename) {
+                    Ok(file) => {
+                        if followed.missing {
+                            eprintln!("{} has appeared; following new file", followed.filename);
+                            followed.missing = false;
+                            followed.offset = 0;
+                        }
+                        followed.file.insert(file)
+                    }
+                    Err(err) => {
+                        if !followed.missing {
+                            eprintln!("{}: {}", followed.filename, err);
+                            followed.missing = true;
+                        }
+                        continue;
+                    }
+                },
+            };
+            let metadata = file.metadata()?;
+            let len = metadata.len();
+            if len < followed.offset {
+                eprintln!("{}: file truncated", followed.filename);
+                followed.offset = 0;
+            }
+            if len == followed.offset {
+                followed.unchanged = followed.unchanged.saturating_add(1);
+                if max_unchanged_stats.is_none_or(|max| followed.unchanged < max) {
+                    continue;
+                }
+                followed.unchanged = 0;
+                match fs::metadata(&followed.filename) {
+                    Err(err) => {
+                        eprintln!("{}: {}", followed.filename, err);
+                        followed.missing = true;
+                        followed.file = None;
+                    }
+                    Ok(current) if !same_file(&metadata, &current) => {
+                        eprintln!(
+                            "{} has been replaced; following new file",
+                            followed.filename
+                        );
+                        followed.file = None;
+                        followed.offset = 0;
+                    }
+                    Ok(_) => {}
+                }
+                continue;
+            }
+            followed.unchanged = 0;
+            if headers && last_index != followed.index {
+                println!("\n==> {} <==", followed.filename);
+                last_index = followed.index;
+            }
+            file.seek(SeekFrom::Start(followed.offset))?;
+            copy_lossy(file.take(len - followed.offset))?;
+            followed.offset = len;
+        }
+        if exited {
+            io::stdout().flush()?;
+            return Ok(());
+        }
+    }
+}
+
+// Two handles refer to the same file if they share a device and inode
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+// Without inodes to compare, a replaced file is only noticed if it is shorter
+#[cfg(not(unix))]
+fn same_file(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    true
+}
+
+// Signal 0 only checks that the process exists; EPERM means it does but
+// belongs to another user
+#[cfg(unix)]
+fn process_alive(pid: i32) -> bool {
+    let signaled = unsafe { libc::kill(pid, 0) } == 0;
+    signaled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// Without a way to probe other processes, --pid never ends following
+#[cfg(not(unix))]
+fn process_alive(_pid: i32) -> bool {
+    true
+}
+
+fn display_name(filename: &str) -> &str {
+    match filename {
+        "-" => "standard input",
+        _ => filename,
+    }
+}
+
+// Counts may carry a GNU-style suffix: b is 512, K/M/G are powers of 1024
+// and KB/MB/GB powers of 1000
 fn parse_count(s: &str) -> MyResult<TakeValue> {
-    if PLUS_ZERO_REG
-        .get_or_init(|| Regex::new(r"^\+0$").unwrap())
-        .is_match(s)
-    {
-        Ok(TakeValue::PlusZero)
-    } else if PLUS_NUM_REG
-        .get_or_init(|| Regex::new(r"^(\+|\-)\d+$").unwrap())
-        .is_match(s)
-    {
-        Ok(TakeValue::TakeNum(s.parse::<i64>().map_err(|_| s)?))
-    } else {
-        Ok(TakeValue::TakeNum(s.parse::<i64>().map_err(|_| s)?.mul(-1)))
+    let caps = COUNT_REG
+        .get_or_init(|| Regex::new(r"^([+-]?)(\d+)(b|[kKmMgG]B?)?$").unwrap())
+        .captures(s)
+        .ok_or(s)?;
+    let multiplier: i128 = match caps.get(3).map(|m| m.as_str()) {
+        None => 1,
+        Some("b") => 512,
+        Some("k" | "K") => 1 << 10,
+        Some("m" | "M") => 1 << 20,
+        Some("g" | "G") => 1 << 30,
+        Some("kB" | "KB") => 1_000,
+        Some("mB" | "MB") => 1_000_000,
+        _ => 1_000_000_000,
+    };
+    let num = caps[2].parse::<i128>().map_err(|_| s)? * multiplier;
+    match &caps[1] {
+        "+" if num == 0 => Ok(TakeValue::PlusZero),
+        "+" => Ok(TakeValue::TakeNum(i64::try_from(num).map_err(|_| s)?)),
+        _ => Ok(TakeValue::TakeNum(i64::try_from(-num).map_err(|_| s)?)),
     }
 }
 
-fn count_lines_bytes(filename: &str) -> MyResult<(i64, i64)> {
-    let file = BufReader::new(File::open(filename)?);
-    let byte_count = file.bytes().count() as i64;
-    let file = BufReader::new(File::open(filename)?);
-    let line_count = file.lines().count() as i64;
+/// Counts lines and bytes in one pass over the handle that will be printed
+/// from, then rewinds it. The byte count is how much that pass read;
+/// callers read no further than that, so data appended afterwards is ignored
+/// and the counts always describe what gets printed.
+fn count_lines_bytes<T: Read + Seek>(file: &mut T) -> MyResult<(i64, i64)> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut line_count = 0;
+    let mut byte_count = 0;
+    let mut last = b'\n';
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        line_count += buf[..n].iter().filter(|&&b| b == b'\n').count() as i64;
+        byte_count += n as i64;
+        last = buf[n - 1];
+    }
+    // A final line without a newline still counts
+    if last != b'\n' {
+        line_count += 1;
+    }
+    file.rewind()?;
 
     Ok((line_count, byte_count))
 }
 
-fn print_lines(mut file: impl BufRead, num_lines: &TakeValue, total_lines: i64) -> MyResult<()> {
+fn print_lines(mut file: impl BufRead, num_lines: &TakeValue, total_lines: i64) -> MyResult<u64> {
     let start = get_start_index(num_lines, total_lines);
+    let mut printed = 0;
     if let Some(s) = start {
-        let mut buf = String::new();
+        // One line is held at a time, however much of the file is printed
+        let mut line = Vec::new();
         for _ in 0..s {
-            file.read_line(&mut buf)?;
+            line.clear();
+            file.read_until(b'\n', &mut line)?;
+        }
+        let mut out = BufWriter::new(io::stdout().lock());
+        loop {
+            line.clear();
+            let bytes = file.read_until(b'\n', &mut line)?;
+            if bytes == 0 {
+                break;
+            }
+            // Invalid bytes are replaced, as print_bytes does
+            out.write_all(String::from_utf8_lossy(&line).as_bytes())?;
+            printed += bytes as u64;
         }
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf)?;
-        print!("{}", String::from_utf8(buf)?);
+        out.flush()?;
     }
 
-    Ok(())
+    Ok(printed)
 }
 
-fn print_bytes<T>(mut file: T, num_bytes: &TakeValue, total_bytes: i64) -> MyResult<()>
+fn print_bytes<T>(mut file: T, num_bytes: &TakeValue, total_bytes: i64) -> MyResult<u64>
 where
     T: Read + Seek,
 {
     let start = get_start_index(num_bytes, total_bytes);
+    let mut printed = 0;
     if let Some(s) = start {
-        file.seek(std::io::SeekFrom::Start(s))?;
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf)?;
-        if !buf.is_empty() {
-            print!("{}", String::from_utf8_lossy(&buf));
+        file.seek(SeekFrom::Start(s))?;
+        printed = copy_lossy(file.take(total_bytes as u64 - s))?;
+    }
+    Ok(printed)
+}
+
+// Streams to stdout with the same replacement as String::from_utf8_lossy;
+// a character split across two chunks is carried over to the next one
+fn copy_lossy(mut reader: impl Read) -> MyResult<u64> {
+    let mut stdout = io::stdout().lock();
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut pending = 0;
+    let mut copied = 0;
+    loop {
+        let n = reader.read(&mut buf[pending..])?;
+        let end = pending + n;
+        let keep = if n == 0 {
+            0
+        } else {
+            incomplete_tail(&buf[..end])
+        };
+        stdout.write_all(String::from_utf8_lossy(&buf[..end - keep]).as_bytes())?;
+        if n == 0 {
+            break;
         }
+        copied += n as u64;
+        buf.copy_within(end - keep..end, 0);
+        pending = keep;
+    }
+    Ok(copied)
+}
+
+// Finds where the last matching line starts, rewinds and prints from there;
+// prints nothing when no line matches
+fn print_from_last_match<T: Read + Seek>(
+    file: &mut T,
+    pattern: &Regex,
+    total_bytes: i64,
+) -> MyResult<u64> {
+    let mut reader = BufReader::new(file.by_ref().take(total_bytes as u64));
+    let mut line = Vec::new();
+    let mut offset = 0;
+    let mut last_match = None;
+    loop {
+        line.clear();
+        let bytes = reader.read_until(b'\n', &mut line)?;
+        if bytes == 0 {
+            break;
+        }
+        let text = String::from_utf8_lossy(&line);
+        if pattern.is_match(text.trim_end_matches(['\n', '\r'])) {
+            last_match = Some(offset);
+        }
+        offset += bytes as i64;
+    }
+    file.rewind()?;
+
+    match last_match {
+        Some(start) => print_bytes(file, &TakeValue::TakeNum(start + 1), total_bytes),
+        None => Ok(0),
     }
-    Ok(())
 }
 
 fn get_start_index(take_val: &TakeValue, total: i64) -> Option<u64> {
@@ -176,7 +572,12 @@ fn get_start_index(take_val: &TakeValue, total: i64) -> Option<u64> {
 
 #[cfg(test)]
 mod tests {
-    use super::{count_lines_bytes, get_start_index, parse_count, TakeValue::*};
+    use super::{count_lines_bytes, get_start_index, parse_count, print_bytes, TakeValue::*};
+    use std::{
+        fs::{File, OpenOptions},
+        io::{Cursor, Write},
+    };
+
     #[test]
     fn test_parse_count() {
         // All integers should be interpreted as negative numbers
@@ -220,16 +621,56 @@ mod tests {
         let res = parse_count("foo");
         assert!(res.is_err());
         assert_eq!(res.unwrap_err().to_string(), "foo");
+        // Suffixes scale the count and keep its sign
+        assert_eq!(parse_count("+1K").unwrap(), TakeNum(1024));
+        assert_eq!(parse_count("2M").unwrap(), TakeNum(-2 * 1024 * 1024));
+        assert_eq!(parse_count("-1g").unwrap(), TakeNum(-1024 * 1024 * 1024));
+        assert_eq!(parse_count("3b").unwrap(), TakeNum(-1536));
+        assert_eq!(parse_count("+2kB").unwrap(), TakeNum(2000));
+        assert_eq!(parse_count("1MB").unwrap(), TakeNum(-1_000_000));
+        assert_eq!(parse_count("+0K").unwrap(), PlusZero);
+        // Unknown suffixes and overflow report the original string
+        let res = parse_count("3Q");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "3Q");
+        let res = parse_count(&format!("+{}G", i64::MAX));
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), format!("+{}G", i64::MAX));
     }
     #[test]
     fn test_count_lines_bytes() {
-        let res = count_lines_bytes("tests/inputs/one.txt");
+        let res = count_lines_bytes(&mut File::open("tests/inputs/one.txt").unwrap());
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), (1, 24));
 
-        let res = count_lines_bytes("tests/inputs/ten.txt");
+        let res = count_lines_bytes(&mut File::open("tests/inputs/ten.txt").unwrap());
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), (10, 49));
+
+        let res = count_lines_bytes(&mut Cursor::new(b"a\xff\nb"));
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), (2, 4));
+
+        let res = count_lines_bytes(&mut Cursor::new(b""));
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), (0, 0));
+    }
+    #[test]
+    fn test_snapshot_ignores_growth() {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(tmp.path(), "one\ntwo\n").unwrap();
+
+        let mut file = File::open(tmp.path()).unwrap();
+        let (lines, bytes) = count_lines_bytes(&mut file).unwrap();
+        assert_eq!((lines, bytes), (2, 8));
+
+        // Grow the file between counting and printing
+        let mut writer = OpenOptions::new().append(true).open(tmp.path()).unwrap();
+        writer.write_all(b"three\n").unwrap();
+
+        // The last 4 bytes of the snapshot, not of the grown file
+        let printed = print_bytes(&mut file, &TakeNum(-4), bytes).unwrap();
+        assert_eq!(printed, 4);
     }
     #[test]
     fn test_get_start_index() {
@@ -257,5 +698,11 @@ mod tests {
         // When starting line/byte is negative and more than total,
         // return 0 to print the whole file
         assert_eq!(get_start_index(&TakeNum(-20), 10), Some(0));
+        // A positive start at the last line/byte still prints it,
+        // while one past the end prints nothing
+        assert_eq!(get_start_index(&TakeNum(9), 10), Some(8));
+        assert_eq!(get_start_index(&TakeNum(10), 10), Some(9));
+        assert_eq!(get_start_index(&TakeNum(11), 10), None);
+        assert_eq!(get_start_index(&TakeNum(i64::MAX), 10), None);
     }
 }
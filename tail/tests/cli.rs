@@ -1,10 +1,12 @@
-use assert_cmd::Command;
+use assert_cmd::{cargo::cargo_bin, Command};
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::fs::{self, File};
-use std::io::Read;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::{process, thread, time::Duration};
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
+type MyResult<T> = Result<T, Box<dyn std::error::Error>>;
 
 const PRG: &str = "tail";
 const EMPTY: &str = "tests/inputs/empty.txt";
@@ -12,6 +14,7 @@ const ONE: &str = "tests/inputs/one.txt";
 const TWO: &str = "tests/inputs/two.txt";
 const THREE: &str = "tests/inputs/three.txt";
 const TEN: &str = "tests/inputs/ten.txt";
+const TRACES: &str = "tests/inputs/traces.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -49,7 +52,7 @@ fn dies_bad_bytes() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal byte count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-c", &bad, EMPTY])
+        .args(["-c", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -57,13 +60,49 @@ fn dies_bad_bytes() -> TestResult {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_bad_suffix() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "3Q", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal byte count -- 3Q"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn size_suffix() -> TestResult {
+    let contents = "x".repeat(1500);
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("suffix.txt");
+    fs::write(&path, &contents)?;
+    let path = path.to_str().unwrap();
+
+    for (count, expected) in [
+        ("1K", 1024),
+        ("+1K", 1500 - 1023),
+        ("1kB", 1000),
+        ("2b", 1024),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(["-c", count, path])
+            .assert()
+            .success()
+            .stdout("x".repeat(expected));
+    }
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn dies_bad_lines() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal line count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-n", &bad, EMPTY])
+        .args(["-n", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -78,7 +117,7 @@ fn dies_bytes_and_lines() -> TestResult {
                used with '--lines <LINES>'";
 
     Command::cargo_bin(PRG)?
-        .args(&["-n", "1", "-c", "2"])
+        .args(["-n", "1", "-c", "2"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(msg));
@@ -92,7 +131,7 @@ fn skips_bad_file() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&[ONE, &bad, TWO])
+        .args([ONE, &bad, TWO])
         .assert()
         .stderr(predicate::str::is_match(expected)?);
 
@@ -790,6 +829,27 @@ fn multiple_files_n_minus_3() -> TestResult {
     )
 }
 
+#[test]
+fn multiple_files_n_plus_2() -> TestResult {
+    run(
+        &["-n", "+2", TEN, EMPTY, ONE, THREE, TWO],
+        "tests/expected/all.n+2.out",
+    )
+}
+
+#[test]
+fn multiple_files_n_plus_2_headers() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "+2", THREE, TWO])
+        .assert()
+        .success()
+        .stdout(
+            "==> tests/inputs/three.txt <==\nlines,\r\nfour words.\n\n\
+             ==> tests/inputs/two.txt <==\nFour words.\n",
+        );
+    Ok(())
+}
+
 #[test]
 fn multiple_files_n_plus_3() -> TestResult {
     run(
@@ -829,3 +889,419 @@ fn multiple_files_c_plus_3() -> TestResult {
         "tests/expected/all.c+3.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn report_size() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--report-size", TEN])
+        .assert()
+        .success()
+        .stderr("printed 49 B\n");
+    Ok(())
+}
+
+#[test]
+fn report_size_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--report-size", "-c", "3", ONE, TEN])
+        .assert()
+        .success()
+        .stderr("printed 6 B\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn from_last_match() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--from-last-match", "^Traceback", TRACES])
+        .assert()
+        .success()
+        .stdout("Traceback 2\n  b\n  c\n");
+    Ok(())
+}
+
+#[test]
+fn from_last_match_none() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--from-last-match", "^Exception", TRACES])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn dies_bad_from_last_match() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--from-last-match", "(", TRACES])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(r#"Invalid --from-last-match "(""#));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_header() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", ONE, "-"])
+        .write_stdin("x\ny\n")
+        .assert()
+        .success()
+        .stdout(
+            "==> tests/inputs/one.txt <==\nÖne line, four wordś.\n\n==> standard input <==\ny\n",
+        );
+    Ok(())
+}
+
+#[test]
+fn stdin_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "+3", "-"])
+        .write_stdin("abcdef")
+        .assert()
+        .success()
+        .stdout("cdef");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn large_positive_byte_offset() -> TestResult {
+    // Several chunks' worth of two-byte characters, so chunk boundaries
+    // fall in the middle of some of them
+    let contents = "Öne line, four wordś.\n".repeat(20_000);
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("large.txt");
+    fs::write(&path, &contents)?;
+
+    let offset = 300_006;
+    let expected = String::from_utf8_lossy(&contents.as_bytes()[offset - 1..]).into_owned();
+    Command::cargo_bin(PRG)?
+        .args(["-c", &format!("+{}", offset), path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+// Runs tail in the background, applies each change to the inputs with a
+// pause before and after, then stops it and returns what it printed
+fn follow_output(args: &[&str], changes: &[&dyn Fn() -> io::Result<()>]) -> MyResult<String> {
+    let mut child = process::Command::new(cargo_bin(PRG))
+        .args(args)
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::null())
+        .spawn()?;
+    let pause = Duration::from_millis(750);
+    thread::sleep(pause);
+    for change in changes {
+        change()?;
+        thread::sleep(pause);
+    }
+    child.kill()?;
+
+    let mut stdout = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut stdout)?;
+    child.wait()?;
+    Ok(stdout)
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_appended() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "one\ntwo\n")?;
+
+    let append = || {
+        OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"three\n")
+    };
+    let truncate = || fs::write(&path, "new\n");
+    let stdout = follow_output(
+        &["-n", "1", "-f", path.to_str().unwrap()],
+        &[&append, &truncate],
+    )?;
+    assert_eq!(stdout, "two\nthree\nnew\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_multiple_headers() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let first = dir.path().join("first.txt");
+    let second = dir.path().join("second.txt");
+    fs::write(&first, "a\n")?;
+    fs::write(&second, "b\n")?;
+
+    let append_first = || {
+        OpenOptions::new()
+            .append(true)
+            .open(&first)?
+            .write_all(b"c\n")
+    };
+    let append_second = || {
+        OpenOptions::new()
+            .append(true)
+            .open(&second)?
+            .write_all(b"d\n")
+    };
+    let (first, second) = (first.to_str().unwrap(), second.to_str().unwrap());
+    let stdout = follow_output(&["-f", first, second], &[&append_second, &append_first])?;
+    assert_eq!(
+        stdout,
+        format!(
+            "==> {0} <==\na\n\n==> {1} <==\nb\nd\n\n==> {0} <==\nc\n",
+            first, second
+        )
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_until_pid_exits() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "one\n")?;
+
+    // Reap the process as soon as it exits, since a zombie still exists
+    let mut sleeper = process::Command::new("sleep").arg("1").spawn()?;
+    let pid = sleeper.id().to_string();
+    let reaper = thread::spawn(move || sleeper.wait());
+
+    Command::cargo_bin(PRG)?
+        .args(["-f", "--pid", &pid, path.to_str().unwrap()])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout("one\n");
+    reaper.join().unwrap()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn pid_without_follow() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--pid", "1", ONE])
+        .assert()
+        .success()
+        .stderr("warning: --pid is only used with --follow and will be ignored\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_pid() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "--pid", "0", ONE])
+        .assert()
+        .failure()
+        .stderr("Invalid --pid \"0\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn plus_offsets_near_end() -> TestResult {
+    let ten = fs::read_to_string(TEN)?;
+    let lines: Vec<_> = ten.split_inclusive('\n').collect();
+    for (count, skip) in [("+1", 0), ("+3", 2), ("+9", 8), ("+10", 9), ("+11", 10)] {
+        Command::cargo_bin(PRG)?
+            .args(["-n", count, TEN])
+            .assert()
+            .success()
+            .stdout(lines[skip..].concat());
+    }
+
+    let len = ten.len();
+    for (count, skip) in [(len - 1, len - 2), (len, len - 1), (len + 1, len)] {
+        Command::cargo_bin(PRG)?
+            .args(["-c", &format!("+{}", count), TEN])
+            .assert()
+            .success()
+            .stdout(ten[skip..].to_string());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_retry_missing_file() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("later.txt");
+
+    let create = || fs::write(&path, "created\n");
+    let append = || {
+        OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"more\n")
+    };
+    for flags in [vec!["-F"], vec!["-f", "--retry"]] {
+        let mut args = flags.clone();
+        args.push(path.to_str().unwrap());
+        let stdout = follow_output(&args, &[&create, &append])?;
+        assert_eq!(stdout, "created\nmore\n");
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_rotated_file() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("log.txt");
+    let rotated = dir.path().join("log.txt.1");
+
+    // The new file is longer than the old one, so it can't pass for a
+    // truncation; only checking the name notices it
+    let rotate = || {
+        fs::rename(&path, &rotated)?;
+        fs::write(&path, "after rotation\n")
+    };
+    for (n, expected) in [("1", "before\nafter rotation\n"), ("100", "before\n")] {
+        fs::write(&path, "before\n")?;
+        let stdout = follow_output(
+            &["-F", "--max-unchanged-stats", n, path.to_str().unwrap()],
+            &[&rotate],
+        )?;
+        assert_eq!(stdout, expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_descriptor_after_rename() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("log.txt");
+    let renamed = dir.path().join("log.txt.1");
+    fs::write(&path, "before\n")?;
+
+    // Plain -f keeps reading the file it opened, whatever the name holds
+    let rotate = || {
+        fs::rename(&path, &renamed)?;
+        fs::write(&path, "new file\n")
+    };
+    let append = || {
+        OpenOptions::new()
+            .append(true)
+            .open(&renamed)?
+            .write_all(b"after rename\n")
+    };
+    let stdout = follow_output(
+        &["-f", "--max-unchanged-stats", "1", path.to_str().unwrap()],
+        &[&rotate, &append],
+    )?;
+    assert_eq!(stdout, "before\nafter rename\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_max_unchanged_stats() -> TestResult {
+    for n in ["0", "x"] {
+        Command::cargo_bin(PRG)?
+            .args(["-f", "--max-unchanged-stats", n, ONE])
+            .assert()
+            .failure()
+            .stderr(format!("Invalid --max-unchanged-stats \"{}\"\n", n));
+    }
+
+    Command::cargo_bin(PRG)?
+        .args(["--max-unchanged-stats", "3", ONE])
+        .assert()
+        .success()
+        .stderr("warning: --max-unchanged-stats is only used with -F and will be ignored\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn retry_without_follow() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--retry", ONE])
+        .assert()
+        .success()
+        .stderr("warning: --retry is only used with --follow and will be ignored\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn lines_from_large_file() -> TestResult {
+    let lines: Vec<_> = (0..50_000).map(|i| format!("line {}", i)).collect();
+    let contents = lines.join("\n");
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("large.txt");
+    fs::write(&path, &contents)?;
+
+    // The last line has no newline and must not gain one
+    Command::cargo_bin(PRG)?
+        .args(["-n", "40000", path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(lines[10_000..].join("\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8_lines() -> TestResult {
+    let input: &[u8] = b"first\nbad \xff byte\nlast\n";
+    Command::cargo_bin(PRG)?
+        .args(["-n", "2", "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("bad \u{FFFD} byte\nlast\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("last\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn verbose_single_file() -> TestResult {
+    for flag in ["-v", "--verbose"] {
+        Command::cargo_bin(PRG)?
+            .args([flag, "-n", "1", ONE])
+            .assert()
+            .success()
+            .stdout("==> tests/inputs/one.txt <==\nÖne line, four wordś.\n");
+    }
+
+    Command::cargo_bin(PRG)?
+        .args(["-v", "-c", "3", "-"])
+        .write_stdin("abcdef")
+        .assert()
+        .success()
+        .stdout("==> standard input <==\ndef");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_verbose_and_quiet() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-v", "-q", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use clap::{App, Arg};
 use std::{
+    borrow::Cow,
+    collections::HashSet,
     error::Error,
     fs::File,
     io::{self, BufRead, BufReader, BufWriter, Write},
@@ -12,6 +14,23 @@ pub struct Config {
     in_file: String,
     out_file: Option<String>,
     count: bool,
+    count_format: CountFormat,
+    assert_sorted: bool,
+    insensitive: bool,
+    last: bool,
+    line_buffered: bool,
+    repeated: bool,
+    unique: bool,
+    json: bool,
+}
+
+/// How counts are rendered with -c, parsed from a spec like "%4d " or "%04d\t".
+#[derive(Debug, PartialEq)]
+struct CountFormat {
+    prefix: String,
+    width: usize,
+    zero_pad: bool,
+    suffix: String,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -37,12 +56,79 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false)
                 .help("Show counts"),
         )
+        .arg(
+            Arg::with_name("count_format")
+                .long("count-format")
+                .value_name("FORMAT")
+                .default_value("%4d ")
+                .help("printf-style format of counts, e.g. \"%d\\t\" or \"%04d \""),
+        )
+        .arg(
+            Arg::with_name("assert_sorted")
+                .long("assert-sorted")
+                .takes_value(false)
+                .help("Fail if a line repeats a non-adjacent earlier line"),
+        )
+        .arg(
+            Arg::with_name("insensitive")
+                .short("i")
+                .long("ignore-case")
+                .takes_value(false)
+                .help("Ignore differences in case when comparing lines"),
+        )
+        .arg(
+            Arg::with_name("first")
+                .long("first")
+                .takes_value(false)
+                .conflicts_with("last")
+                .help("Print the first line of each group (default)"),
+        )
+        .arg(
+            Arg::with_name("last")
+                .long("last")
+                .takes_value(false)
+                .help("Print the last line of each group"),
+        )
+        .arg(
+            Arg::with_name("line_buffered")
+                .long("line-buffered")
+                .takes_value(false)
+                .help("Flush output after each group"),
+        )
+        .arg(
+            Arg::with_name("repeated")
+                .short("d")
+                .long("repeated")
+                .takes_value(false)
+                .help("Only print groups with more than one line"),
+        )
+        .arg(
+            Arg::with_name("unique")
+                .short("u")
+                .long("unique")
+                .takes_value(false)
+                .help("Only print groups of a single line"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help("Print one JSON object per group with its count and line"),
+        )
         .get_matches();
 
     Ok(Config {
         in_file: matches.value_of("in_file").unwrap().to_string(),
         out_file: matches.value_of("out_file").map(str::to_string),
         count: matches.is_present("count"),
+        count_format: parse_count_format(matches.value_of("count_format").unwrap())?,
+        assert_sorted: matches.is_present("assert_sorted"),
+        insensitive: matches.is_present("insensitive"),
+        last: matches.is_present("last"),
+        line_buffered: matches.is_present("line_buffered"),
+        repeated: matches.is_present("repeated"),
+        unique: matches.is_present("unique"),
+        json: matches.is_present("json"),
     })
 }
 
@@ -67,33 +153,178 @@ pub fn run(config: Config) -> MyResult<()> {
     let mut line = String::new();
     let mut bytes = file.read_line(&mut line)?;
     let mut prev_line = line.clone();
+    // The line printed for the current group, which differs from prev_line
+    // only with --last
+    let mut representative = line.clone();
     let mut count: usize = 0;
+    let mut line_num = 1;
+    // Keys of groups already written, only kept when checking sortedness
+    let mut seen = config.assert_sorted.then(HashSet::new);
+    // With -i, the key of prev_line is kept instead of lowercasing it again
+    // for every line
+    let mut prev_key = config
+        .insensitive
+        .then(|| group_key(&prev_line, true).into_owned());
     loop {
-        if bytes == 0 && prev_line.len() == 0 {
+        if bytes == 0 && prev_line.is_empty() {
             break;
         }
 
-        if line.trim() == prev_line.trim() {
+        let key = group_key(&line, config.insensitive);
+        let prev = prev_key.as_deref().unwrap_or_else(|| prev_line.trim());
+        if key == prev {
             count += 1;
+            if config.last {
+                representative = line.clone();
+            }
         } else {
-            outfile.write_all(
-                format!("{}{}", format_field(count, config.count), prev_line).as_bytes(),
-            )?;
+            // As with GNU uniq, -d and -u together print nothing
+            if (count > 1 || !config.repeated) && (count == 1 || !config.unique) {
+                outfile.write_all(format_group(count, &representative, &config).as_bytes())?;
+                if config.line_buffered {
+                    outfile.flush()?;
+                }
+            }
             count = 1;
+
+            if let Some(seen) = seen.as_mut() {
+                seen.insert(prev.to_string());
+                if bytes > 0 && seen.contains(key.as_ref()) {
+                    return Err(From::from(format!(
+                        "{}: line {} repeats an earlier non-adjacent line \"{}\"; \
+                         sort the input first",
+                        config.in_file,
+                        line_num,
+                        line.trim()
+                    )));
+                }
+            }
+            if config.insensitive {
+                prev_key = Some(key.into_owned());
+            }
             prev_line = line.clone();
+            representative = line.clone();
         }
 
         line.clear();
         bytes = file.read_line(&mut line)?;
+        line_num += 1;
     }
     outfile.flush().unwrap();
     Ok(())
 }
 
-fn format_field(value: usize, is_present: bool) -> String {
-    if is_present {
-        format!("{:>4} ", value)
+// Lines belong to the same group when their keys are equal
+fn group_key(line: &str, insensitive: bool) -> Cow<'_, str> {
+    if insensitive {
+        Cow::Owned(line.trim().to_lowercase())
     } else {
+        Cow::Borrowed(line.trim())
+    }
+}
+
+fn format_group(count: usize, line: &str, config: &Config) -> String {
+    if config.json {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        format!("{}\n", serde_json::json!({ "count": count, "line": line }))
+    } else {
+        format!(
+            "{}{}",
+            format_field(count, config.count, &config.count_format),
+            line
+        )
+    }
+}
+
+fn format_field(value: usize, is_present: bool, format: &CountFormat) -> String {
+    if !is_present {
         "".to_string()
+    } else if format.zero_pad {
+        format!(
+            "{}{:0width$}{}",
+            format.prefix,
+            value,
+            format.suffix,
+            width = format.width
+        )
+    } else {
+        format!(
+            "{}{:>width$}{}",
+            format.prefix,
+            value,
+            format.suffix,
+            width = format.width
+        )
+    }
+}
+
+fn parse_count_format(spec: &str) -> MyResult<CountFormat> {
+    let err = || format!("Invalid --count-format \"{}\"", spec);
+    let unescaped = spec.replace("\\t", "\t");
+    let (prefix, rest) = unescaped.split_once('%').ok_or_else(err)?;
+    let (width, suffix) = rest.split_once('d').ok_or_else(err)?;
+    if suffix.contains('%') || !width.chars().all(|c| c.is_ascii_digit()) {
+        return Err(From::from(err()));
+    }
+    Ok(CountFormat {
+        prefix: prefix.to_string(),
+        width: if width.is_empty() { 0 } else { width.parse()? },
+        zero_pad: width.starts_with('0'),
+        suffix: suffix.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_field, parse_count_format, CountFormat};
+
+    #[test]
+    fn test_parse_count_format() {
+        let res = parse_count_format("%4d ");
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            CountFormat {
+                prefix: "".to_string(),
+                width: 4,
+                zero_pad: false,
+                suffix: " ".to_string(),
+            }
+        );
+
+        let res = parse_count_format("[%03d]\\t");
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            CountFormat {
+                prefix: "[".to_string(),
+                width: 3,
+                zero_pad: true,
+                suffix: "]\t".to_string(),
+            }
+        );
+
+        for bad in ["", "%", "4d", "%xd", "%d%d", "%-4d"] {
+            let res = parse_count_format(bad);
+            assert!(res.is_err());
+            assert_eq!(
+                res.unwrap_err().to_string(),
+                format!("Invalid --count-format \"{}\"", bad)
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_field() {
+        let default = parse_count_format("%4d ").unwrap();
+        assert_eq!(format_field(3, false, &default), "");
+        assert_eq!(format_field(3, true, &default), "   3 ");
+        assert_eq!(format_field(12345, true, &default), "12345 ");
+
+        let tab = parse_count_format("%d\t").unwrap();
+        assert_eq!(format_field(3, true, &tab), "3\t");
+
+        let zero = parse_count_format("%04d ").unwrap();
+        assert_eq!(format_field(3, true, &zero), "0003 ");
     }
 }
@@ -124,7 +124,7 @@ fn run(test: &Test) -> TestResult {
 fn run_count(test: &Test) -> TestResult {
     let expected = fs::read_to_string(test.out_count)?;
     Command::cargo_bin(PRG)?
-        .args(&[test.input, "-c"])
+        .args([test.input, "-c"])
         .assert()
         .success()
         .stdout(expected);
@@ -162,12 +162,12 @@ fn run_outfile(test: &Test) -> TestResult {
     let outfile = NamedTempFile::new()?;
     let outpath = &outfile.path().to_str().unwrap();
     Command::cargo_bin(PRG)?
-        .args(&[test.input, outpath])
+        .args([test.input, outpath])
         .assert()
         .success()
         .stdout("");
 
-    let contents = fs::read_to_string(&outpath)?;
+    let contents = fs::read_to_string(outpath)?;
     assert_eq!(&expected, &contents);
 
     Ok(())
@@ -179,13 +179,13 @@ fn run_outfile_count(test: &Test) -> TestResult {
     let outpath = &outfile.path().to_str().unwrap();
 
     Command::cargo_bin(PRG)?
-        .args(&[test.input, outpath, "--count"])
+        .args([test.input, outpath, "--count"])
         .assert()
         .success()
         .stdout("");
 
     let expected = fs::read_to_string(test.out_count)?;
-    let contents = fs::read_to_string(&outpath)?;
+    let contents = fs::read_to_string(outpath)?;
     assert_eq!(&expected, &contents);
 
     Ok(())
@@ -198,13 +198,13 @@ fn run_stdin_outfile_count(test: &Test) -> TestResult {
     let outpath = &outfile.path().to_str().unwrap();
 
     Command::cargo_bin(PRG)?
-        .args(&["-", outpath, "-c"])
+        .args(["-", outpath, "-c"])
         .write_stdin(input)
         .assert()
         .stdout("");
 
     let expected = fs::read_to_string(test.out_count)?;
-    let contents = fs::read_to_string(&outpath)?;
+    let contents = fs::read_to_string(outpath)?;
     assert_eq!(&expected, &contents);
 
     Ok(())
@@ -605,3 +605,176 @@ fn t6_outfile_count() -> TestResult {
 fn t6_stdin_outfile_count() -> TestResult {
     run_stdin_outfile_count(&T6)
 }
+
+// --------------------------------------------------
+#[test]
+fn t5_count_format_tab() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([T5.input, "-c", "--count-format", "%d\t"])
+        .assert()
+        .success()
+        .stdout("1\tb\n2\ta\n");
+    Ok(())
+}
+
+#[test]
+fn t5_count_format_zero_pad() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([T5.input, "-c", "--count-format", "%03d "])
+        .assert()
+        .success()
+        .stdout("001 b\n002 a\n");
+    Ok(())
+}
+
+#[test]
+fn dies_bad_count_format() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([T5.input, "-c", "--count-format", "%x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --count-format \"%x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn three_assert_sorted() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([THREE.input, "--assert-sorted"])
+        .assert()
+        .failure()
+        .stdout("a\nb\n")
+        .stderr(predicate::str::contains(
+            "line 5 repeats an earlier non-adjacent line \"a\"; sort the input first",
+        ));
+    Ok(())
+}
+
+#[test]
+fn t6_assert_sorted() -> TestResult {
+    let expected = fs::read_to_string(T6.out)?;
+    Command::cargo_bin(PRG)?
+        .args([T6.input, "--assert-sorted"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn insensitive_first_last() -> TestResult {
+    let input = "apple\nApple\nAPPLE\nbanana\nBanana\n";
+    for (args, expected) in [
+        (vec!["-i"], "apple\nbanana\n"),
+        (vec!["-i", "--first"], "apple\nbanana\n"),
+        (vec!["-i", "--last"], "APPLE\nBanana\n"),
+        (vec!["-i", "-c", "--last"], "   3 APPLE\n   2 Banana\n"),
+        (vec!["--last"], "apple\nApple\nAPPLE\nbanana\nBanana\n"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn dies_first_and_last() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--first", "--last"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn repeated_unique() -> TestResult {
+    let input = "a\na\nb\nc\nc\nc\n";
+    for (args, expected) in [
+        (vec!["-d"], "a\nc\n"),
+        (vec!["-u"], "b\n"),
+        (vec!["-c", "-d"], "   2 a\n   3 c\n"),
+        (vec!["-d", "-u"], ""),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn json() -> TestResult {
+    let input = "a\na\n\"quoted\"\nc\nc\nc";
+    for (args, expected) in [
+        (
+            vec!["--json"],
+            "{\"count\":2,\"line\":\"a\"}\n\
+             {\"count\":1,\"line\":\"\\\"quoted\\\"\"}\n\
+             {\"count\":3,\"line\":\"c\"}\n",
+        ),
+        (
+            vec!["--json", "-d"],
+            "{\"count\":2,\"line\":\"a\"}\n{\"count\":3,\"line\":\"c\"}\n",
+        ),
+        (
+            vec!["--json", "-u"],
+            "{\"count\":1,\"line\":\"\\\"quoted\\\"\"}\n",
+        ),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn line_buffered_flushes_each_group() -> TestResult {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{Command as StdCommand, Stdio};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let mut child = StdCommand::new(assert_cmd::cargo::cargo_bin(PRG))
+        .arg("--line-buffered")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    // The "a" group is complete once "b" is read, while stdin is still open
+    stdin.write_all(b"a\na\nb\n")?;
+    stdin.flush()?;
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || loop {
+        let mut line = String::new();
+        if stdout.read_line(&mut line).unwrap_or(0) == 0 || tx.send(line).is_err() {
+            break;
+        }
+    });
+    let first = rx.recv_timeout(Duration::from_secs(10));
+
+    drop(stdin);
+    assert!(child.wait()?.success());
+    assert_eq!(first?, "a\n");
+    assert_eq!(rx.recv()?, "b\n");
+    Ok(())
+}
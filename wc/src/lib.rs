@@ -12,6 +12,16 @@ pub struct Config {
     words: bool,
     bytes: bool,
     chars: bool,
+    total: Total,
+}
+
+/// When to print the total row, as in GNU wc's --total.
+#[derive(Debug, PartialEq)]
+enum Total {
+    Auto,
+    Always,
+    Only,
+    Never,
 }
 
 #[derive(Debug, PartialEq)]
@@ -63,6 +73,14 @@ pub fn get_args() -> MyResult<Config> {
                 .conflicts_with("bytes")
                 .help("Show character count"),
         )
+        .arg(
+            Arg::with_name("total")
+                .long("total")
+                .value_name("WHEN")
+                .possible_values(&["auto", "always", "only", "never"])
+                .default_value("auto")
+                .help("When to print a total row; \"only\" prints just the totals"),
+        )
         .get_matches();
 
     let mut lines = matches.is_present("lines");
@@ -82,6 +100,12 @@ pub fn get_args() -> MyResult<Config> {
         words,
         bytes,
         chars,
+        total: match matches.value_of("total") {
+            Some("always") => Total::Always,
+            Some("only") => Total::Only,
+            Some("never") => Total::Never,
+            _ => Total::Auto,
+        },
     })
 }
 
@@ -99,6 +123,9 @@ pub fn run(config: Config) -> MyResult<()> {
                     total_num_words += info.num_words;
                     total_num_bytes += info.num_bytes;
                     total_num_chars += info.num_chars;
+                    if config.total == Total::Only {
+                        continue;
+                    }
                     println!(
                         "{}{}{}{}{}",
                         format_field(info.num_lines, config.lines),
@@ -116,13 +143,23 @@ pub fn run(config: Config) -> MyResult<()> {
         };
     }
 
-    if config.files.len() > 1 {
+    let show_total = match config.total {
+        Total::Auto => config.files.len() > 1,
+        Total::Always | Total::Only => true,
+        Total::Never => false,
+    };
+    if show_total {
         println!(
-            "{}{}{}{} total",
+            "{}{}{}{}{}",
             format_field(total_num_lines, config.lines),
             format_field(total_num_words, config.words),
             format_field(total_num_bytes, config.bytes),
-            format_field(total_num_chars, config.chars)
+            format_field(total_num_chars, config.chars),
+            if config.total == Total::Only {
+                ""
+            } else {
+                " total"
+            }
         );
     }
 
@@ -29,7 +29,7 @@ fn gen_bad_file() -> String {
 #[test]
 fn dies_chars_and_bytes() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&["-m", "-c"])
+        .args(["-m", "-c"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(
@@ -212,3 +212,32 @@ fn test_all_words_lines() -> TestResult {
 fn test_all_bytes_lines() -> TestResult {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn test_all_total_only() -> TestResult {
+    run(
+        &["--total", "only", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.total_only.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_total_never() -> TestResult {
+    run(
+        &["--total=never", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.total_never.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_total_always() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--total", "always", "-l", FOX])
+        .assert()
+        .success()
+        .stdout("       1 tests/inputs/fox.txt\n       1 total\n");
+    Ok(())
+}